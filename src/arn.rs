@@ -0,0 +1,63 @@
+//! ARNs, split into their fields. The partition isn't always `aws`: GovCloud is
+//! `aws-us-gov` and China is `aws-cn`, so nothing assumes an `arn:aws:` prefix.
+
+/// `arn:PARTITION:SERVICE:REGION:ACCOUNT:RESOURCE`, where the resource may
+/// itself contain `:` (e.g. `function:NAME:ALIAS`).
+#[derive(Debug, PartialEq, Eq)]
+pub struct Arn<'a> {
+    pub partition: &'a str,
+    pub service: &'a str,
+    /// Empty for global resources.
+    pub region: &'a str,
+    /// Empty for resources not owned by an account.
+    pub account_id: &'a str,
+    pub resource: &'a str,
+}
+
+impl<'a> Arn<'a> {
+    pub fn parse(arn: &'a str) -> Option<Self> {
+        let mut fields = arn.splitn(6, ':');
+        if fields.next()? != "arn" {
+            return None;
+        }
+        let arn = Arn {
+            partition: fields.next()?,
+            service: fields.next()?,
+            region: fields.next()?,
+            account_id: fields.next()?,
+            resource: fields.next()?,
+        };
+        (!arn.partition.is_empty()).then_some(arn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_any_partition() {
+        let arn =
+            Arn::parse("arn:aws-us-gov:lambda:us-gov-west-1:123456789012:function:scraper:live")
+                .unwrap();
+        assert_eq!(
+            arn,
+            Arn {
+                partition: "aws-us-gov",
+                service: "lambda",
+                region: "us-gov-west-1",
+                account_id: "123456789012",
+                resource: "function:scraper:live",
+            }
+        );
+
+        assert_eq!(
+            Arn::parse("arn:aws-cn:lambda:cn-north-1:123456789012:function:scraper")
+                .unwrap()
+                .partition,
+            "aws-cn"
+        );
+        assert!(Arn::parse("not-an-arn").is_none());
+        assert!(Arn::parse("arn:aws:lambda").is_none());
+    }
+}
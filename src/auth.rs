@@ -0,0 +1,207 @@
+//! How requests to GitHub are authenticated: with a personal token, or as a
+//! GitHub App installation for automation where personal tokens aren't allowed.
+
+use crate::{exit::AuthError, health};
+use anyhow::{anyhow, Result};
+use octocrab::{
+    models::{AppId, Installation, InstallationId, InstallationToken},
+    Octocrab,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::Mutex;
+
+/// A new installation token is requested this long before the current one expires.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// How long installation tokens last, for when GitHub doesn't say.
+const TOKEN_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+pub enum GitHubAuth {
+    Token {
+        client: Arc<Octocrab>,
+        /// A hash of the token, see [`GitHubAuth::identity`].
+        hash: u64,
+    },
+    App {
+        /// Authenticated as the app itself, which can only request installation tokens.
+        app: Box<Octocrab>,
+        installation: InstallationId,
+        /// Shared by every request until its token is about to expire.
+        current: Mutex<InstallationClient>,
+    },
+}
+
+pub struct InstallationClient {
+    client: Arc<Octocrab>,
+    expires_at: SystemTime,
+}
+
+impl GitHubAuth {
+    pub fn token(token: String) -> Result<Self> {
+        let mut hasher = DefaultHasher::new();
+        token.hash(&mut hasher);
+        Ok(GitHubAuth::Token {
+            hash: hasher.finish(),
+            client: Arc::new(Octocrab::builder().personal_token(token).build()?),
+        })
+    }
+
+    /// Authenticates as installation `installation` of the app, or when that's
+    /// not given, the app's installation on `owner`, an org or a user.
+    pub async fn app(
+        app_id: u64,
+        key_path: &Path,
+        installation: Option<u64>,
+        owner: &str,
+    ) -> Result<Self> {
+        let pem = fs::read(key_path).map_err(|e| {
+            anyhow!(
+                "Failed to read GitHub App key {}: {}",
+                key_path.display(),
+                e
+            )
+        })?;
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(&pem).map_err(|e| {
+            anyhow!(
+                "Failed to parse GitHub App key {}: {}",
+                key_path.display(),
+                e
+            )
+        })?;
+        let app = Octocrab::builder().app(AppId(app_id), key).build()?;
+
+        let installation = match installation {
+            Some(id) => InstallationId(id),
+            None => find_installation(&app, owner).await?,
+        };
+        let current = installation_client(&app, installation).await.map_err(|e| {
+            AuthError(format!(
+                "GitHub: failed to get a token for installation {}: {}",
+                installation, e
+            ))
+        })?;
+        Ok(GitHubAuth::App {
+            app: Box::new(app),
+            installation,
+            current: Mutex::new(current),
+        })
+    }
+
+    /// A client to send requests with. Take a new one for each unit of work, so a
+    /// long run picks up refreshed installation tokens.
+    pub async fn client(&self) -> Result<Arc<Octocrab>, octocrab::Error> {
+        match self {
+            GitHubAuth::Token { client, .. } => Ok(client.clone()),
+            GitHubAuth::App {
+                app,
+                installation,
+                current,
+            } => {
+                // Held while refreshing, so concurrent requests wait for one new
+                // token rather than each asking for their own.
+                let mut current = current.lock().await;
+                if needs_refresh(current.expires_at, SystemTime::now()) {
+                    *current = installation_client(app, *installation).await?;
+                }
+                Ok(current.client.clone())
+            }
+        }
+    }
+
+    /// Tells one token or installation apart from another without revealing
+    /// the token, for keeping what each can read apart.
+    pub fn identity(&self) -> String {
+        match self {
+            GitHubAuth::Token { hash, .. } => format!("token {:016x}", hash),
+            GitHubAuth::App { installation, .. } => format!("installation {}", installation),
+        }
+    }
+}
+
+/// Requests an installation token and builds the client every request shares
+/// until it expires. Installation clients from octocrab itself drop their token
+/// when cloned, so each clone would request another.
+async fn installation_client(
+    app: &Octocrab,
+    installation: InstallationId,
+) -> Result<InstallationClient, octocrab::Error> {
+    let route = format!("/app/installations/{}/access_tokens", installation);
+    let token: InstallationToken = app.post(route, None::<&()>).await?;
+    let expires_at = token
+        .expires_at
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or_else(|| SystemTime::now() + TOKEN_LIFETIME);
+    let client = Octocrab::builder().personal_token(token.token).build()?;
+    Ok(InstallationClient {
+        client: Arc::new(client),
+        expires_at,
+    })
+}
+
+fn needs_refresh(expires_at: SystemTime, now: SystemTime) -> bool {
+    now + REFRESH_MARGIN >= expires_at
+}
+
+/// An RFC 3339 UTC timestamp as GitHub writes them, e.g. `2026-10-14T12:00:00Z`.
+fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let days = u64::try_from(health::parse_date(timestamp)?).ok()?;
+    let mut time = timestamp.get(11..19)?.split(':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    let since_epoch = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(since_epoch))
+}
+
+/// The app's installation on an org, falling back to a user account.
+async fn find_installation(app: &Octocrab, owner: &str) -> Result<InstallationId> {
+    let org = app
+        .get::<Installation, _, _>(format!("/orgs/{}/installation", owner), None::<&()>)
+        .await;
+    let installation = match org {
+        Ok(installation) => Ok(installation),
+        Err(octocrab::Error::GitHub { source, .. }) if source.message == "Not Found" => {
+            app.get(format!("/users/{}/installation", owner), None::<&()>)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    installation
+        .map(|installation| installation.id)
+        .map_err(|e| {
+            AuthError(format!(
+                "GitHub: no installation of the app found for {}: {}",
+                owner, e
+            ))
+            .into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refreshes_shortly_before_the_token_expires() {
+        let expires_at = parse_timestamp("2026-10-14T12:00:00Z").unwrap();
+        assert_eq!(
+            expires_at.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
+            Duration::from_secs(1_791_979_200)
+        );
+        assert!(parse_timestamp("2026-10-14").is_none());
+
+        let minutes_before = |minutes: u64| expires_at - Duration::from_secs(minutes * 60);
+        assert!(!needs_refresh(expires_at, minutes_before(30)));
+        assert!(needs_refresh(expires_at, minutes_before(5)));
+        assert!(needs_refresh(
+            expires_at,
+            expires_at + Duration::from_secs(1)
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+//! Reuses function listings: within a run whenever the same target is listed
+//! again, and across runs for `--aws-cache-ttl` seconds. `--refresh` skips both.
+//!
+//! GitHub responses are kept across runs with their ETag. Within `--ttl` seconds
+//! they're reused as they are; after that GitHub is asked whether they changed,
+//! and a "not modified" answer doesn't count against the rate limit. They're
+//! kept per token or installation, so one never sees what only another could
+//! read.
+
+use crate::Lambda;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub struct ListingCache {
+    /// `None` keeps the cache in memory only.
+    ttl: Option<Duration>,
+    refresh: bool,
+    memory: Mutex<HashMap<String, Vec<Lambda>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Seconds since the Unix epoch.
+    listed_at: u64,
+    functions: Vec<Lambda>,
+}
+
+impl ListingCache {
+    pub fn new(ttl: Option<Duration>, refresh: bool) -> Self {
+        ListingCache {
+            ttl,
+            refresh,
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether listings are kept across runs, not just within this one.
+    pub fn persistent(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<Lambda>> {
+        if self.refresh {
+            return None;
+        }
+        if let Some(functions) = self.memory.lock().unwrap().get(key) {
+            return Some(functions.clone());
+        }
+
+        let ttl = self.ttl?;
+        let entry = read_file::<Entry>(&cache_file("functions.json")?)?.remove(key)?;
+        let age = now().saturating_sub(entry.listed_at);
+        if age > ttl.as_secs() {
+            return None;
+        }
+        progress!("Using the function list cached {}s ago", age);
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), entry.functions.clone());
+        Some(entry.functions)
+    }
+
+    pub fn put(&self, key: &str, functions: &[Lambda]) {
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), functions.to_vec());
+        if self.ttl.is_none() {
+            return;
+        }
+
+        let Some(path) = cache_file("functions.json") else {
+            return;
+        };
+        let mut entries = read_file(&path).unwrap_or_default();
+        let entry = Entry {
+            listed_at: now(),
+            functions: functions.to_vec(),
+        };
+        entries.insert(key.to_string(), entry);
+        write_file(&path, &entries, "function");
+    }
+}
+
+/// GitHub responses, keyed by who fetched them and the URI they were fetched
+/// from, which names the repo and ref. Written out once, when dropped.
+pub struct ContentCache {
+    /// `None` when caching is off.
+    path: Option<PathBuf>,
+    ttl: Duration,
+    /// Prefixes every key, see [`crate::auth::GitHubAuth::identity`].
+    identity: String,
+    responses: Mutex<BTreeMap<String, Response>>,
+    changed: AtomicBool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub etag: Option<String>,
+    /// Seconds since the Unix epoch.
+    fetched_at: u64,
+    pub body: String,
+}
+
+impl ContentCache {
+    /// With `enabled` false, nothing is read or kept. `identity` names the
+    /// credentials responses are fetched with.
+    pub fn new(ttl: Duration, enabled: bool, identity: &str) -> Self {
+        let path = enabled.then(|| cache_file("github.json")).flatten();
+        ContentCache::open(path, ttl, identity)
+    }
+
+    fn open(path: Option<PathBuf>, ttl: Duration, identity: &str) -> Self {
+        let responses = path.as_deref().and_then(read_file).unwrap_or_default();
+        ContentCache {
+            path,
+            ttl,
+            identity: identity.to_string(),
+            responses: Mutex::new(responses),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    fn key(&self, uri: &str) -> String {
+        format!("{} {}", self.identity, uri)
+    }
+
+    /// The cached response for `uri`, and whether it's recent enough to use
+    /// without asking GitHub. With a TTL of zero, it never is.
+    pub fn get(&self, uri: &str) -> Option<(Response, bool)> {
+        let response = self
+            .responses
+            .lock()
+            .unwrap()
+            .get(&self.key(uri))
+            .cloned()?;
+        let age = now().saturating_sub(response.fetched_at);
+        let fresh = !self.ttl.is_zero() && age <= self.ttl.as_secs();
+        Some((response, fresh))
+    }
+
+    pub fn put(&self, uri: &str, etag: Option<String>, body: String) {
+        if self.path.is_none() {
+            return;
+        }
+        let response = Response {
+            etag,
+            fetched_at: now(),
+            body,
+        };
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(self.key(uri), response);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Restarts the TTL of a response GitHub said is unchanged.
+    pub fn revalidated(&self, uri: &str) {
+        let Some((response, _)) = self.get(uri) else {
+            return;
+        };
+        self.put(uri, response.etag, response.body);
+    }
+}
+
+impl Drop for ContentCache {
+    fn drop(&mut self) {
+        if let (Some(path), true) = (&self.path, *self.changed.get_mut()) {
+            write_file(path, self.responses.get_mut().unwrap(), "GitHub");
+        }
+    }
+}
+
+/// An unreadable or outdated cache file is treated as empty.
+fn read_file<T: DeserializeOwned>(path: &Path) -> Option<BTreeMap<String, T>> {
+    let content = fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// A cache that can't be written only costs time on the next run.
+fn write_file<T: Serialize>(path: &Path, entries: &BTreeMap<String, T>, what: &str) {
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_vec(entries).map_err(|e| e.to_string()))
+        .and_then(|content| write_private(path, &content).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        warning!("failed to write {} cache {}: {}", what, path.display(), e);
+    }
+}
+
+/// Writes `content` readable by the current user only, as caches and
+/// checkpoints hold function configuration and private repos' files.
+pub fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    restrict(&file)?;
+    file.write_all(content)
+}
+
+/// The mode given when opening only applies to new files, so one written by an
+/// older version is tightened here.
+#[cfg(unix)]
+fn restrict(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Under `XDG_CACHE_HOME` or `~/.cache`, or `%LOCALAPPDATA%` on Windows.
+fn cache_file(name: &str) -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join("beacon2").join(name))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_responses_across_runs() {
+        let path = env::temp_dir().join(format!("beacon2-github-cache-{}", std::process::id()));
+        let uri = "/repos/Mikeysauce/scraper/contents/package.json?ref=main";
+        let minute = Duration::from_secs(60);
+        let cache = ContentCache::open(Some(path.clone()), minute, "token 1");
+        cache.put(uri, Some("\"abc\"".to_string()), "{}".to_string());
+        assert!(!path.exists(), "written on every put");
+        drop(cache);
+
+        let (response, fresh) = ContentCache::open(Some(path.clone()), minute, "token 1")
+            .get(uri)
+            .unwrap();
+        assert_eq!(response.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(response.body, "{}");
+        assert!(fresh);
+
+        // Past the TTL the response is still there to revalidate by its ETag,
+        // and a TTL of zero always revalidates.
+        let stale = ContentCache::open(Some(path.clone()), minute, "token 1");
+        stale
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(&stale.key(uri))
+            .unwrap()
+            .fetched_at -= 61;
+        assert!(!stale.get(uri).unwrap().1);
+        let uncached = ContentCache::open(Some(path.clone()), Duration::ZERO, "token 1");
+        assert!(!uncached.get(uri).unwrap().1);
+
+        // Other credentials may not be able to see the repo at all.
+        assert!(ContentCache::open(Some(path.clone()), minute, "token 2")
+            .get(uri)
+            .is_none());
+        assert!(ContentCache::open(None, minute, "token 1")
+            .get(uri)
+            .is_none());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! `--checkpoint`: progress saved as the run goes, so an interrupted scan can be
+//! picked up with `--resume` instead of starting over.
+//!
+//! Only repos that were read successfully are recorded, so failures are retried
+//! on resume. Each listing records the marker of the next page to fetch.
+
+use crate::{cache, Lambda, RepoDetails};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+pub struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    repos: BTreeMap<String, SavedRepo>,
+    /// Keyed by the AWS target the functions were listed from.
+    listings: BTreeMap<String, Listing>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRepo {
+    version: Option<Value>,
+    package_name: Option<String>,
+    commit: Option<String>,
+}
+
+/// A listing so far. `next_marker` is `None` once every page was fetched.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Listing {
+    pub next_marker: Option<String>,
+    pub total_functions: usize,
+    pub functions: Vec<Lambda>,
+}
+
+impl Checkpoint {
+    /// With `resume`, continues from what `path` already holds; otherwise starts afresh.
+    pub fn open(path: &Path, resume: bool) -> Result<Self> {
+        let state = if resume {
+            let content = fs::read_to_string(path)
+                .map_err(|e| anyhow!("Failed to read checkpoint {}: {}", path.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse checkpoint {}: {}", path.display(), e))?
+        } else {
+            State::default()
+        };
+
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn repo(&self, repo: &str) -> Option<RepoDetails> {
+        let state = self.state.lock().unwrap();
+        state.repos.get(repo).map(|saved| RepoDetails {
+            version: Ok(saved.version.clone()),
+            package_name: saved.package_name.clone(),
+            commit: saved.commit.clone(),
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    pub fn record_repo(&self, repo: &str, details: &RepoDetails) {
+        let Ok(version) = &details.version else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let saved = SavedRepo {
+            version: version.clone(),
+            package_name: details.package_name.clone(),
+            commit: details.commit.clone(),
+        };
+        state.repos.insert(repo.to_string(), saved);
+        self.save(&state);
+    }
+
+    pub fn listing(&self, target: &str) -> Option<Listing> {
+        self.state.lock().unwrap().listings.get(target).cloned()
+    }
+
+    pub fn record_listing(&self, target: &str, listing: Listing) {
+        let mut state = self.state.lock().unwrap();
+        state.listings.insert(target.to_string(), listing);
+        self.save(&state);
+    }
+
+    /// Removes the file once a run completes, so the next run starts afresh.
+    pub fn finish(&self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warning!("failed to remove checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// A checkpoint that can't be written only costs the ability to resume, so
+    /// it is warned about rather than failing the run.
+    fn save(&self, state: &State) {
+        let written = serde_json::to_vec(state)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                cache::write_private(&self.path, &content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = written {
+            warning!("failed to write checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FetchError;
+    use serde_json::json;
+
+    fn details(version: Result<Option<Value>, FetchError>) -> RepoDetails {
+        RepoDetails {
+            version,
+            package_name: None,
+            commit: Some("abc123".to_string()),
+            elapsed: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn resumes_successful_repos_only() {
+        let path = std::env::temp_dir().join(format!("beacon2-checkpoint-{}", std::process::id()));
+        let checkpoint = Checkpoint::open(&path, false).unwrap();
+        checkpoint.record_repo("Mikeysauce/scraper", &details(Ok(Some(json!("1.2.0")))));
+        let failed = FetchError::NoAccess {
+            owner: "Mikeysauce".to_string(),
+            repo: "movies-front".to_string(),
+        };
+        checkpoint.record_repo("Mikeysauce/movies-front", &details(Err(failed)));
+
+        let resumed = Checkpoint::open(&path, true).unwrap();
+        let scraper = resumed.repo("Mikeysauce/scraper").unwrap();
+        assert_eq!(scraper.version.unwrap(), Some(json!("1.2.0")));
+        assert_eq!(scraper.commit.as_deref(), Some("abc123"));
+        assert!(resumed.repo("Mikeysauce/movies-front").is_none());
+
+        resumed.finish();
+        assert!(!path.exists());
+    }
+}
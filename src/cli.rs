@@ -0,0 +1,635 @@
+use crate::{
+    compare::{Precision, Version, VersionSource},
+    config::{self, Config},
+    matching::{MatchKey, MatchRule, MatchStrategy},
+    matrix::Environment,
+    report::OutputTemplate,
+    repos::{self, Repo},
+    watch,
+};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr, time::Duration};
+
+/// Compare package.json versions in GitHub against deployed Lambda functions.
+#[derive(Parser, Debug)]
+#[command(version, about, after_help = crate::exit::HELP)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Read defaults from this TOML or YAML file; command-line flags take precedence.
+    /// Defaults to `stuff.toml` in the working directory, when there is one
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Repos listed in `--config`, used instead of the defaults.
+    #[arg(skip)]
+    pub config_repos: Vec<Repo>,
+
+    /// Per-repo matching rules from `--config`.
+    #[arg(skip)]
+    pub match_rules: BTreeMap<String, MatchRule>,
+
+    /// GitHub token to read repos with
+    #[arg(long, value_name = "TOKEN", env = "MY_TOKEN", hide_env_values = true)]
+    pub github_token: Option<String>,
+
+    /// Authenticate as this GitHub App instead of with a token; needs `--github-app-key`
+    #[arg(
+        long,
+        value_name = "ID",
+        env = "GITHUB_APP_ID",
+        requires = "github_app_key"
+    )]
+    pub github_app_id: Option<u64>,
+
+    /// PEM private key of the GitHub App
+    #[arg(long, value_name = "FILE", env = "GITHUB_APP_KEY_PATH")]
+    pub github_app_key: Option<PathBuf>,
+
+    /// Installation of the GitHub App to act as. Found from `--org` or `--owner`
+    /// when not given
+    #[arg(long, value_name = "ID", env = "GITHUB_APP_INSTALLATION_ID")]
+    pub github_installation_id: Option<u64>,
+
+    /// Owner for repos given without one, in the default list, `--repos-stdin`,
+    /// and `--config`
+    #[arg(long, default_value = repos::DEFAULT_OWNER)]
+    pub owner: String,
+
+    /// Only print results, warnings, and errors, not progress
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Only consider functions built for this instruction set architecture
+    #[arg(long, value_enum)]
+    pub architecture: Option<Architecture>,
+
+    /// Only consider functions attached to this VPC
+    #[arg(long, value_name = "VPC_ID")]
+    pub vpc_id: Option<String>,
+
+    /// Only consider functions whose environment sets KEY to exactly VALUE;
+    /// repeat to require several
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub env_filter: Vec<EnvFilter>,
+
+    /// Read the repos to scan from stdin, one `owner/repo` per line
+    /// (blank lines and `#` comments are ignored)
+    #[arg(long)]
+    pub repos_stdin: bool,
+
+    /// Scan every repo owned by this GitHub org or user instead of a fixed list.
+    /// Archived repos are left out
+    #[arg(long, value_name = "OWNER", conflicts_with = "repos_stdin")]
+    pub org: Option<String>,
+
+    /// With `--org`, only scan repos tagged with one of these topics (comma-separated)
+    #[arg(long, value_name = "TOPICS", value_delimiter = ',', requires = "org")]
+    pub topic: Vec<String>,
+
+    /// With `--org`, only scan repos whose primary language is this, e.g. `TypeScript`
+    #[arg(long, value_name = "LANGUAGE", requires = "org")]
+    pub language: Option<String>,
+
+    /// With `--org`, only scan repos whose name matches this regex
+    #[arg(long, value_name = "REGEX", requires = "org")]
+    pub name_pattern: Option<Regex>,
+
+    /// Only scan these repos (comma-separated `repo` or `owner/repo`)
+    #[arg(long, value_name = "REPOS", value_delimiter = ',')]
+    pub only_repos: Vec<String>,
+
+    /// Don't scan these repos (comma-separated `repo` or `owner/repo`)
+    #[arg(long, value_name = "REPOS", value_delimiter = ',')]
+    pub skip_repos: Vec<String>,
+
+    /// How many repos to read, or AWS targets to list, at the same time
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 8,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub concurrency: usize,
+
+    /// Refuse to scan more repos than this unless `--yes` is given
+    #[arg(long, value_name = "N", default_value_t = 200)]
+    pub max_repos: usize,
+
+    /// Scan even when there are more repos than `--max-repos`
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Save progress (repos read, listing pages fetched) to this file as the run goes;
+    /// it is removed once a run completes
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Continue an interrupted run from `--checkpoint` instead of starting over
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Flag matched functions that have no dead-letter queue configured
+    #[arg(long)]
+    pub check_dlq: bool,
+
+    /// Show how much provisioned concurrency matched functions have configured
+    #[arg(long)]
+    pub show_concurrency: bool,
+
+    /// Read from GitHub and list Lambda functions at the same time, instead of one
+    /// after the other
+    #[arg(long)]
+    pub parallel_regions_and_github: bool,
+
+    /// Read package.json files from `<repo>.json` fixtures in this directory instead of GitHub
+    #[arg(long, value_name = "DIR")]
+    pub fixtures: Option<PathBuf>,
+
+    /// Read the expected version from this plain file in each repo (e.g. `deploy.lock`)
+    /// instead of package.json's `version` field
+    #[arg(long, value_name = "PATH", conflicts_with = "fixtures")]
+    pub version_file: Option<String>,
+
+    /// Read the expected version from the `version` field of this JSON asset
+    /// (e.g. `version.json`) on each repo's latest release
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["fixtures", "version_file", "git_ref"]
+    )]
+    pub release_asset: Option<String>,
+
+    /// Refuse to parse repo files or release assets larger than this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 5 * 1024 * 1024)]
+    pub max_file_size: u64,
+
+    /// Read repo files at this branch, tag, or commit SHA instead of the default branch
+    #[arg(long = "ref", value_name = "REF")]
+    pub git_ref: Option<String>,
+
+    /// Read repo files at each repo's latest GitHub release tag, to compare what's
+    /// deployed against the last release instead of the default branch
+    #[arg(
+        long,
+        conflicts_with_all = ["git_ref", "release_asset", "fixtures"]
+    )]
+    pub latest_release: bool,
+
+    /// Look for package.json files throughout each repo's tree, and compare each
+    /// one found below the root as its own service, named after its directory.
+    /// Repos whose packages are listed in `--config` are left as listed
+    #[arg(long)]
+    pub discover_packages: bool,
+
+    /// Check whether a branch (default `main`) is already deployed: reads package.json
+    /// from that branch and labels the report as a branch-vs-deployed comparison
+    #[arg(
+        long,
+        value_name = "BRANCH",
+        num_args = 0..=1,
+        default_missing_value = "main",
+        conflicts_with_all = ["git_ref", "latest_release", "version_file", "release_asset", "fixtures"]
+    )]
+    pub compare_branch_vs_deployed: Option<String>,
+
+    /// AWS region to list functions in, overriding the environment and profile
+    #[arg(long)]
+    pub region: Option<String>,
+
+    /// AWS profile to list functions with, instead of the default credential chain
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// List functions in each of these regions (comma-separated) instead of one
+    #[arg(
+        long,
+        value_name = "REGIONS",
+        value_delimiter = ',',
+        conflicts_with = "region"
+    )]
+    pub regions: Vec<String>,
+
+    /// IAM role to assume via STS before listing functions. The base credentials come
+    /// from the default chain, including GitHub Actions OIDC web identity
+    #[arg(long, value_name = "ARN")]
+    pub role_arn: Option<String>,
+
+    /// Assume this role in a target account and list its functions too; repeat once
+    /// per account. The region defaults to `--region` or the default chain
+    #[arg(long, value_name = "ARN[@REGION]", conflicts_with = "role_arn")]
+    pub assume_role: Vec<AssumeRole>,
+
+    /// Send AWS requests to this endpoint instead, e.g. `http://localhost:4566` for LocalStack
+    #[arg(long, value_name = "URL", env = "AWS_ENDPOINT_URL")]
+    pub aws_endpoint_url: Option<String>,
+
+    /// Reuse function lists cached by a previous run up to this many seconds ago.
+    /// They're cached per AWS account, which is looked up with STS
+    #[arg(long, value_name = "SECONDS")]
+    pub aws_cache_ttl: Option<u64>,
+
+    /// List functions afresh, ignoring any cached function list
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Reuse GitHub responses cached up to this many seconds ago without asking
+    /// GitHub; older ones are only re-downloaded if they changed. Also the default
+    /// for `--aws-cache-ttl`
+    #[arg(long, value_name = "SECONDS")]
+    pub ttl: Option<u64>,
+
+    /// Don't read or write any cache on disk
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Show how long each repo took to fetch
+    #[arg(long)]
+    pub timings: bool,
+
+    /// How repo names are matched to function names
+    #[arg(long, value_enum, default_value_t = MatchStrategy::Contains)]
+    pub match_strategy: MatchStrategy,
+
+    /// Which name is matched against function names
+    #[arg(long, value_enum, default_value_t = MatchKey::Repo)]
+    pub match_key: MatchKey,
+
+    /// Stage names `--match-strategy stage` strips from function names (comma-separated)
+    #[arg(
+        long,
+        value_name = "STAGES",
+        value_delimiter = ',',
+        default_value = "dev,test,staging,prod"
+    )]
+    pub stages: Vec<String>,
+
+    /// The function tag `--match-strategy tag` reads the repo from
+    #[arg(long, value_name = "KEY", default_value = "repo")]
+    pub match_tag: String,
+
+    /// Drop the `@scope/` from package names before matching with `--match-key name`
+    #[arg(long)]
+    pub strip_scope: bool,
+
+    /// Look each repo's function up by this exact name instead, e.g. `acme-{repo}-prod`.
+    /// `{repo}` is the repo name, `{name}` the package.json name without its scope
+    #[arg(long, value_name = "TEMPLATE")]
+    pub function_name_template: Option<String>,
+
+    /// Minimum similarity (0.0 to 1.0) for `--match-strategy fuzzy`
+    #[arg(long, value_name = "SCORE", default_value_t = 0.85, value_parser = parse_fuzzy_threshold)]
+    pub fuzzy_threshold: f64,
+
+    /// Browse the results interactively instead of printing them
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "format")]
+    pub tui: bool,
+
+    /// How to print the results. Progress and warnings always go to stderr, so
+    /// stdout holds only the results
+    #[arg(long, visible_alias = "output", value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Make the output byte-for-byte reproducible for the same results, e.g. to keep
+    /// it in git: keys and lists are sorted, and run-dependent output like
+    /// `--timings` is refused
+    #[arg(long, conflicts_with = "timings")]
+    pub stable: bool,
+
+    /// After the report, show what changed since a previous run's `--format json`
+    /// or `--format ndjson` output
+    #[arg(long, value_name = "FILE")]
+    pub diff_against: Option<PathBuf>,
+
+    /// At the end, print the exit code, a count per status, and every error and
+    /// warning of the run to stderr
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Write the end-of-run summary to this file as JSON
+    #[arg(long, value_name = "FILE")]
+    pub summary_json: Option<PathBuf>,
+
+    /// Run this shell command once per matched function, with its result as JSON on stdin
+    #[arg(long, value_name = "CMD")]
+    pub on_result: Option<String>,
+
+    /// Run this shell command once at the end, with all results as a JSON array on stdin
+    #[arg(long, value_name = "CMD")]
+    pub on_complete: Option<String>,
+
+    /// Post a summary of drift, missing functions, and errors to this Slack incoming webhook
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "SLACK_WEBHOOK_URL",
+        hide_env_values = true
+    )]
+    pub slack_webhook: Option<String>,
+
+    /// Publish a summary of drift, missing functions, and errors to this SNS topic
+    #[arg(long, value_name = "ARN")]
+    pub sns_topic_arn: Option<String>,
+
+    /// Only notify when there are at least this many findings
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub notify_threshold: usize,
+
+    /// After the report, list deployed functions that matched none of the repos
+    #[arg(long)]
+    pub orphans: bool,
+
+    /// After the report, summarize env var keys across the matched functions
+    #[arg(long)]
+    pub env_audit: bool,
+
+    /// After the report, show env vars that differ between the functions each repo
+    /// matches, e.g. the same service deployed to several regions
+    #[arg(long)]
+    pub compare_env_between_functions: bool,
+
+    /// Print, per repo, which functions were considered and why one did or didn't match
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Group the report into Drift / Up to date / No Lambda found / Errors sections
+    #[arg(long)]
+    pub group_by_status: bool,
+
+    /// Print each result as this line instead of the report, e.g.
+    /// `{repo} {function} {pkg_version} {deployed_version} {status}`.
+    /// Also available: `{arn}` and `{commit}`
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["format", "group_by_status"])]
+    pub template: Option<OutputTemplate>,
+
+    /// Print environment variable values that look like secrets instead of
+    /// `<redacted>`. Function lists are then never cached
+    #[arg(long, conflicts_with_all = ["redact_keys", "allow_keys"])]
+    pub show_secrets: bool,
+
+    /// Also redact the values of env vars whose names match this regex; repeatable
+    #[arg(long, value_name = "REGEX")]
+    pub redact_keys: Vec<Regex>,
+
+    /// Never redact the values of env vars whose names match this regex; repeatable
+    #[arg(long, value_name = "REGEX")]
+    pub allow_keys: Vec<Regex>,
+
+    /// Environment variable holding a function's deployed version
+    #[arg(long, value_name = "KEY", default_value = "APP_VERSION")]
+    pub version_env_var: String,
+
+    /// Where to read a function's deployed version: `env:KEY`, `tag:KEY`,
+    /// `ssm:NAME` (may contain `{function}`), `package:PATH` (a file inside the
+    /// downloaded deployment package), `http:URL` (a JSON version endpoint; may contain
+    /// `{function}`), or `description`. Defaults to `env:` with `--version-env-var`
+    #[arg(long, value_name = "SOURCE", conflicts_with = "version_env_var")]
+    pub version_source: Option<VersionSource>,
+
+    /// Refuse to download deployment packages larger than this many bytes with
+    /// `--version-source package:PATH`
+    #[arg(long, value_name = "BYTES", default_value_t = 50 * 1024 * 1024)]
+    pub max_package_size: u64,
+
+    /// Give up on a version endpoint after this many seconds with `--version-source http:URL`
+    #[arg(long, value_name = "SECONDS", default_value_t = 5)]
+    pub http_timeout: u64,
+
+    /// Strip this suffix (e.g. `-staging`) from deployed versions before comparing
+    #[arg(long, value_name = "SUFFIX")]
+    pub ignore_version_suffix: Option<String>,
+
+    /// Compare versions only down to this component, ignoring the rest
+    #[arg(long, value_enum, value_name = "PRECISION")]
+    pub compare_precision: Option<Precision>,
+
+    /// Take the deployed version from this regex's first capture group (or whole
+    /// match). A `description` source defaults to the first `X.Y.Z`-like token
+    #[arg(long, value_name = "REGEX")]
+    pub version_regex: Option<Regex>,
+
+    /// Call out repos whose manifest has no version, as a warning or (affecting
+    /// the exit code) an error
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SEVERITY",
+        num_args = 0..=1,
+        default_missing_value = "warn"
+    )]
+    pub warn_missing_version: Option<Severity>,
+
+    /// Don't count a repo without a deployed function towards the drift exit code,
+    /// e.g. when some repos are libraries
+    #[arg(long)]
+    pub allow_missing_function: bool,
+
+    /// Flag repos whose version is below this, e.g. `2.0.0` after a breaking
+    /// migration, whatever is deployed
+    #[arg(long, value_name = "VERSION", value_parser = parse_min_version)]
+    pub min_version: Option<Version>,
+
+    /// Instead of comparing against Lambda, check that every repo declares the
+    /// same version of this dependency (from `dependencies` or `devDependencies`)
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["version_file", "release_asset"])]
+    pub check_dependency: Option<String>,
+
+    /// Version the checked dependency should be at, instead of the most common one
+    #[arg(long, value_name = "VERSION", requires = "check_dependency")]
+    pub expected: Option<String>,
+
+    /// Instead of comparing versions, check every repo's dependencies against this
+    /// TOML or YAML policy of `banned` packages and `minimum` versions, and list the
+    /// deployed functions running violating code
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["check_dependency", "version_file", "release_asset"]
+    )]
+    pub dependency_policy: Option<PathBuf>,
+}
+
+/// Parses the command line, filling in whatever it leaves unset from `--config`.
+pub fn parse() -> anyhow::Result<Args> {
+    let matches = Args::command()
+        .try_get_matches()
+        .unwrap_or_else(|e| usage_error(e));
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // `validate` reports every problem itself rather than stopping at the first.
+    let validating = matches!(args.command, Some(Command::Validate));
+    if args.config.is_none() {
+        args.config = Some(PathBuf::from(config::DEFAULT_PATH)).filter(|path| path.exists());
+    }
+    if let (Some(path), false) = (&args.config, validating) {
+        Config::load(path)?.apply(&mut args, &matches)?;
+    }
+
+    Ok(args)
+}
+
+/// Exits like clap would, but with `exit::FAILED` rather than 2, which means
+/// fetch failures. `--help` and `--version` still exit 0.
+fn usage_error(e: clap::Error) -> ! {
+    if !e.use_stderr() {
+        e.exit();
+    }
+    let _ = e.print();
+    std::process::exit(crate::exit::FAILED);
+}
+
+/// Also accepts the floor written as a requirement, e.g. `>=2.0.0`.
+fn parse_min_version(s: &str) -> Result<Version, String> {
+    s.trim_start_matches(">=").parse()
+}
+
+/// The same range `fuzzy-threshold` is held to in `--config`.
+fn parse_fuzzy_threshold(s: &str) -> Result<f64, String> {
+    let threshold: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("must be between 0.0 and 1.0, got {}", threshold));
+    }
+    Ok(threshold)
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compare the repos' versions against the deployed functions. This is also
+    /// what runs without a subcommand
+    Compare,
+    /// Report on the health of the deployed functions instead of their versions:
+    /// outdated Node runtimes and, with `--stale-days`, functions left untouched
+    Report {
+        /// Flag Node runtimes older than this major version
+        #[arg(long, value_name = "MAJOR", default_value_t = 22)]
+        min_node_major: u32,
+        /// Flag functions not modified in this many days
+        #[arg(long, value_name = "DAYS")]
+        stale_days: Option<i64>,
+    },
+    /// List the deployed functions that pass the filters, without reading any repos
+    ListLambdas,
+    /// List the repos that would be scanned, without reading them
+    ListRepos,
+    /// Show a services-by-environments table of deployed versions alongside package.json
+    CompareAllVersions {
+        /// An environment column, as NAME=PROFILE:REGION (e.g. `prod=acme-prod:eu-west-1`)
+        #[arg(long = "env", value_name = "NAME=PROFILE:REGION", required = true)]
+        environments: Vec<Environment>,
+    },
+    /// Check `--config` and the flags that refer to it, without calling GitHub or AWS
+    Validate,
+    /// Keep running and compare again on an interval, writing the results and
+    /// sending notifications only when they changed since the previous scan
+    Watch {
+        /// Time between scans, in seconds or with an `m`, `h`, or `d` suffix
+        #[arg(long, value_name = "INTERVAL", default_value = "15m", value_parser = watch::parse_interval)]
+        interval: Duration,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable report
+    Text,
+    /// One JSON object per result, one per line
+    Ndjson,
+    /// A JUnit XML test suite with one test case per repo
+    Junit,
+    /// Every result in one JSON array, e.g. for jq
+    Json,
+    /// Every result as a YAML list
+    Yaml,
+    /// One aligned row per repo
+    Table,
+}
+
+impl Format {
+    /// Whether stdout must hold nothing but the results, so it stays parseable.
+    pub fn machine_readable(self) -> bool {
+        !matches!(self, Format::Text | Format::Table)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Severity {
+    Warn,
+    Error,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Warn => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Architecture {
+    Arm64,
+    #[value(name = "x86_64")]
+    X86_64,
+}
+
+impl Architecture {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Architecture::Arm64 => "arm64",
+            Architecture::X86_64 => "x86_64",
+        }
+    }
+}
+
+/// An environment variable a function must have, written as `KEY=VALUE`.
+#[derive(Clone, Debug)]
+pub struct EnvFilter {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for EnvFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok(EnvFilter {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!("expected KEY=VALUE, got `{}`", s)),
+        }
+    }
+}
+
+/// A cross-account target, written as `ARN[@REGION]` on the command line.
+#[derive(Clone, Debug)]
+pub struct AssumeRole {
+    pub role_arn: String,
+    pub region: Option<String>,
+}
+
+impl FromStr for AssumeRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (role_arn, region) = match s.split_once('@') {
+            Some((role_arn, region)) if !region.is_empty() => (role_arn, Some(region)),
+            Some(_) => return Err(format!("missing region after `@` in `{}`", s)),
+            None => (s, None),
+        };
+
+        if !role_arn.starts_with("arn:") {
+            return Err(format!("expected a role ARN, got `{}`", role_arn));
+        }
+
+        Ok(AssumeRole {
+            role_arn: role_arn.to_string(),
+            region: region.map(str::to_string),
+        })
+    }
+}
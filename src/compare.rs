@@ -0,0 +1,647 @@
+//! Pairs each repo with its deployed function and decides whether they agree.
+
+use crate::{
+    error::FetchError,
+    matching::{MatchOutcome, Matcher},
+    Lambda, RepoDetails,
+};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Drift,
+    UpToDate,
+    /// A function matched, but it doesn't expose a deployed version to compare.
+    Unknown,
+    /// Several functions matched equally well, so none was compared.
+    Ambiguous,
+    /// The repo's manifest was fetched but has no version.
+    MissingVersion,
+    NoLambda,
+    Error,
+}
+
+impl Status {
+    pub const ALL: [Status; 7] = [
+        Status::Drift,
+        Status::UpToDate,
+        Status::Unknown,
+        Status::Ambiguous,
+        Status::MissingVersion,
+        Status::NoLambda,
+        Status::Error,
+    ];
+
+    pub fn heading(&self) -> &'static str {
+        match self {
+            Status::Drift => "Drift",
+            Status::UpToDate => "Up to date",
+            Status::Unknown => "Unknown deployed version",
+            Status::Ambiguous => "Ambiguous match",
+            Status::MissingVersion => "No version in repo",
+            Status::NoLambda => "No Lambda found",
+            Status::Error => "Errors",
+        }
+    }
+}
+
+/// One repo's outcome. This is what every output format renders.
+#[derive(Serialize)]
+pub struct Comparison<'a> {
+    pub repo: &'a str,
+    #[serde(rename = "function")]
+    pub lambda: Option<&'a Lambda>,
+    /// Similarity score when the function was fuzzy-matched.
+    pub match_score: Option<f64>,
+    /// The function name `--function-name-template` gave for this repo.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_function: Option<String>,
+    /// The equally good functions when the match was ambiguous.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<&'a str>,
+    /// The version from the repo; `None` when it couldn't be fetched or is absent.
+    pub expected: Option<String>,
+    pub deployed: Option<&'a str>,
+    /// The commit `expected` was read at.
+    pub commit: Option<&'a str>,
+    pub status: Status,
+    /// The repo's version is below `--min-version`, whatever is deployed.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub below_minimum: bool,
+    /// Why the repo couldn't be fetched.
+    pub error: Option<&'a FetchError>,
+}
+
+#[cfg(test)]
+impl<'a> Comparison<'a> {
+    /// A comparison with nothing but its outcome, for tests to fill in.
+    pub fn new(repo: &'a str, status: Status) -> Self {
+        Comparison {
+            repo,
+            lambda: None,
+            match_score: None,
+            expected_function: None,
+            candidates: Vec::new(),
+            expected: None,
+            deployed: None,
+            commit: None,
+            status,
+            below_minimum: false,
+            error: None,
+        }
+    }
+}
+
+/// Where a function's deployed version is read from, written as `env:KEY`,
+/// `tag:KEY`, `ssm:NAME`, `package:PATH`, `http:URL`, or `description` on the
+/// command line. An SSM parameter name or URL may contain `{function}`, e.g.
+/// `ssm:/apps/{function}/version`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionSource {
+    Env(String),
+    Tag(String),
+    /// A parameter name template.
+    Ssm(String),
+    /// A file inside the deployment package, e.g. `VERSION`. Reading it means
+    /// downloading the package, so it's only done when asked for.
+    Package(String),
+    /// A URL template for a version endpoint answering with the running version,
+    /// as JSON with a `version` field or as plain text.
+    Http(String),
+    Description,
+}
+
+impl FromStr for VersionSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':').unwrap_or((s, "")) {
+            ("env", key) if !key.is_empty() => Ok(VersionSource::Env(key.to_string())),
+            ("tag", key) if !key.is_empty() => Ok(VersionSource::Tag(key.to_string())),
+            ("ssm", name) if !name.is_empty() => Ok(VersionSource::Ssm(name.to_string())),
+            ("package", path) if !path.is_empty() => Ok(VersionSource::Package(path.to_string())),
+            ("http", url) if !url.is_empty() => Ok(VersionSource::Http(url.to_string())),
+            ("description", "") => Ok(VersionSource::Description),
+            _ => Err(format!(
+                "expected env:KEY, tag:KEY, ssm:NAME, package:PATH, http:URL, or description, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl VersionSource {
+    /// Where the version is looked for, as shown in the report.
+    pub fn label(&self) -> String {
+        match self {
+            VersionSource::Env(key) => format!("env var {}", key),
+            VersionSource::Tag(key) => format!("tag {}", key),
+            VersionSource::Ssm(name) => format!("SSM parameter {}", name),
+            VersionSource::Package(path) => format!("package file {}", path),
+            VersionSource::Http(url) => format!("the response from {}", url),
+            VersionSource::Description => "the description".to_string(),
+        }
+    }
+}
+
+/// Picks a version-looking token out of free text such as a description.
+const DEFAULT_DESCRIPTION_PATTERN: &str = r"\d+\.\d+\.\d+[0-9A-Za-z.+-]*";
+
+/// Reads a function's deployed version from its source, optionally narrowed by a regex.
+pub struct DeployedVersion {
+    pub source: VersionSource,
+    /// The version is this pattern's first capture group, or its whole match
+    /// when it has none.
+    pub pattern: Option<Regex>,
+    /// Stripped from the deployed version before comparing, e.g. `-staging`.
+    pub ignore_suffix: Option<String>,
+    /// Compare only this many leading version components; `None` compares exactly.
+    pub precision: Option<Precision>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Precision {
+    /// `1.4.2` equals `1.9.0`
+    Major,
+    /// `1.4.2` equals `1.4.9`
+    Minor,
+    /// `1.4.2` equals `1.4.2-rc.1`
+    Patch,
+}
+
+impl Precision {
+    /// The leading `major[.minor[.patch]]` of `version`, without pre-release or
+    /// build metadata. Versions with fewer components are returned as they are.
+    fn truncate(self, version: &str) -> &str {
+        let core = version.split(['-', '+']).next().unwrap_or(version);
+        let components = match self {
+            Precision::Major => 1,
+            Precision::Minor => 2,
+            Precision::Patch => 3,
+        };
+        match core.match_indices('.').nth(components - 1) {
+            Some((end, _)) => &core[..end],
+            None => core,
+        }
+    }
+}
+
+impl DeployedVersion {
+    /// Descriptions are free text, so without a `pattern` they're searched for
+    /// something shaped like a version.
+    pub fn new(source: VersionSource, pattern: Option<Regex>) -> Self {
+        let pattern = match (&source, pattern) {
+            (VersionSource::Description, None) => {
+                Some(Regex::new(DEFAULT_DESCRIPTION_PATTERN).expect("valid default pattern"))
+            }
+            (_, pattern) => pattern,
+        };
+        DeployedVersion {
+            source,
+            pattern,
+            ignore_suffix: None,
+            precision: None,
+        }
+    }
+
+    /// Whether `deployed` is `expected`, once `ignore_suffix` is dropped from it
+    /// and both are cut to `precision`. Versions are compared as semver when both
+    /// parse, so `v1.2` is `1.2.0`, and as plain strings otherwise.
+    pub fn matches(&self, expected: &str, deployed: &str) -> bool {
+        let deployed = self
+            .ignore_suffix
+            .as_deref()
+            .and_then(|suffix| deployed.strip_suffix(suffix))
+            .unwrap_or(deployed);
+        let (expected, deployed) = match self.precision {
+            Some(precision) => (precision.truncate(expected), precision.truncate(deployed)),
+            None => (expected, deployed),
+        };
+        match (expected.parse::<Version>(), deployed.parse::<Version>()) {
+            (Ok(expected), Ok(deployed)) => expected == deployed,
+            _ => expected == deployed,
+        }
+    }
+
+    pub fn read<'a>(&self, fnc: &'a Lambda) -> Option<&'a str> {
+        let value = match &self.source {
+            VersionSource::Env(key) => fnc.env_vars.get(key)?,
+            VersionSource::Tag(key) => fnc.tags.get(key)?,
+            VersionSource::Ssm(_) => fnc.ssm_version.as_ref()?,
+            VersionSource::Package(_) => fnc.package_version.as_ref()?,
+            VersionSource::Http(_) => fnc.http_version.as_ref()?,
+            VersionSource::Description => fnc.description.as_ref()?,
+        };
+
+        let Some(pattern) = &self.pattern else {
+            return Some(value.trim());
+        };
+        let captures = pattern.captures(value)?;
+        captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|version| version.as_str())
+    }
+}
+
+/// A `major[.minor[.patch]][-pre][+build]` version, with an optional leading `v`.
+/// Ordered as semver orders them, except that pre-release tags are compared as
+/// plain strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Option<String>,
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a version like 2.0.0, got `{}`", s);
+        let core = s.trim().trim_start_matches('v');
+        let core = core.split('+').next().unwrap_or(core);
+        let (core, pre) = match core.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (core, None),
+        };
+
+        let mut components = core.split('.').map(|c| c.parse::<u64>());
+        let mut next = |required: bool| match components.next() {
+            Some(component) => component.map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        };
+        let version = Version {
+            major: next(true)?,
+            minor: next(false)?,
+            patch: next(false)?,
+            pre,
+        };
+        match components.next() {
+            Some(_) => Err(invalid()),
+            None => Ok(version),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        match &self.pre {
+            Some(pre) => write!(f, "-{}", pre),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(pre), Some(other)) => pre.cmp(other),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Marks repos whose version is below `minimum`, leaving their status alone.
+/// Versions that don't parse aren't marked.
+pub fn apply_minimum(comparisons: &mut [Comparison], minimum: &Version) {
+    for comparison in comparisons {
+        comparison.below_minimum = comparison
+            .expected
+            .as_deref()
+            .and_then(|expected| expected.parse::<Version>().ok())
+            .is_some_and(|version| version < *minimum);
+    }
+}
+
+/// package.json versions are JSON strings; anything else is compared by its JSON text.
+pub fn version_string(version: &Value) -> String {
+    match version {
+        Value::String(version) => version.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn compare<'a>(
+    details: &'a BTreeMap<String, RepoDetails>,
+    deployed_lambdas: &'a [Lambda],
+    deployed_version: &DeployedVersion,
+    matcher: &Matcher,
+) -> Vec<Comparison<'a>> {
+    details
+        .iter()
+        .flat_map(|(repo, details)| {
+            let package_name = details.package_name.as_deref();
+            matcher
+                .find_each_location(repo, package_name, deployed_lambdas)
+                .into_iter()
+                .map(move |outcome| (repo, details, outcome))
+        })
+        .map(|(repo, details, outcome)| {
+            let expected = details
+                .version
+                .as_ref()
+                .ok()
+                .and_then(Option::as_ref)
+                .map(version_string);
+            let error = details.version.as_ref().err();
+            let expected_function = matcher.expected_name(repo, details.package_name.as_deref());
+            let (lambda, match_score, candidates) = match outcome {
+                MatchOutcome::Matched { lambda, score } => (Some(lambda), score, Vec::new()),
+                MatchOutcome::Ambiguous(lambdas) => (
+                    None,
+                    None,
+                    lambdas.iter().map(|fnc| fnc.name.as_str()).collect(),
+                ),
+                MatchOutcome::NotFound => (None, None, Vec::new()),
+            };
+            let deployed = lambda.and_then(|fnc| deployed_version.read(fnc));
+
+            let status = match (&expected, lambda, deployed) {
+                (None, _, _) if error.is_some() => Status::Error,
+                (None, _, _) => Status::MissingVersion,
+                (Some(_), None, _) if !candidates.is_empty() => Status::Ambiguous,
+                (Some(_), None, _) => Status::NoLambda,
+                (Some(_), Some(_), None) => Status::Unknown,
+                (Some(expected), Some(_), Some(deployed))
+                    if deployed_version.matches(expected, deployed) =>
+                {
+                    Status::UpToDate
+                }
+                (Some(_), Some(_), Some(_)) => Status::Drift,
+            };
+
+            Comparison {
+                repo,
+                lambda,
+                match_score,
+                expected_function,
+                candidates,
+                expected,
+                deployed,
+                commit: details.commit.as_deref(),
+                status,
+                below_minimum: false,
+                error,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, time::Duration};
+
+    fn lambda(name: &str, version: Option<&str>) -> Lambda {
+        Lambda {
+            name: name.to_string(),
+            env_vars: version
+                .map(|v| HashMap::from([("APP_VERSION".to_string(), v.to_string())]))
+                .unwrap_or_default(),
+            arn: format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name),
+            architectures: vec!["x86_64".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn details(version: Result<Option<&str>, &str>) -> RepoDetails {
+        RepoDetails {
+            version: version
+                .map(|v| v.map(|v| Value::String(v.to_string())))
+                .map_err(|message| FetchError::File {
+                    owner: "Mikeysauce".to_string(),
+                    repo: "broken".to_string(),
+                    git_ref: None,
+                    path: "package.json".to_string(),
+                    message: message.to_string(),
+                }),
+            package_name: None,
+            commit: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    fn app_version() -> DeployedVersion {
+        DeployedVersion::new(VersionSource::Env("APP_VERSION".to_string()), None)
+    }
+
+    #[test]
+    fn reads_the_version_from_the_description() {
+        let fnc = Lambda {
+            description: Some("Scraper, deployed-version: 1.2.3-rc.1".to_string()),
+            ..Default::default()
+        };
+        let source: VersionSource = "description".parse().unwrap();
+
+        assert_eq!(
+            DeployedVersion::new(source, None).read(&fnc),
+            Some("1.2.3-rc.1")
+        );
+        assert!("tag:".parse::<VersionSource>().is_err());
+    }
+
+    #[test]
+    fn ignored_suffix_is_only_stripped_from_the_deployed_version() {
+        let deployed_version = DeployedVersion {
+            ignore_suffix: Some("-staging".to_string()),
+            ..app_version()
+        };
+
+        assert!(deployed_version.matches("1.4.2", "1.4.2-staging"));
+        assert!(deployed_version.matches("1.4.2", "1.4.2"));
+        assert!(!deployed_version.matches("1.4.2-staging", "1.4.2"));
+    }
+
+    fn at(precision: Precision) -> DeployedVersion {
+        DeployedVersion {
+            precision: Some(precision),
+            ..app_version()
+        }
+    }
+
+    #[test]
+    fn major_precision_ignores_minor_and_patch() {
+        assert!(at(Precision::Major).matches("1.4.2", "1.9.0"));
+        assert!(!at(Precision::Major).matches("1.4.2", "2.4.2"));
+    }
+
+    #[test]
+    fn minor_precision_ignores_patch() {
+        assert!(at(Precision::Minor).matches("1.4.2", "1.4.9"));
+        assert!(!at(Precision::Minor).matches("1.4.2", "1.5.2"));
+    }
+
+    #[test]
+    fn patch_precision_ignores_pre_release_and_build() {
+        assert!(at(Precision::Patch).matches("1.4.2", "1.4.2-rc.1+build.7"));
+        assert!(!at(Precision::Patch).matches("1.4.2", "1.4.3"));
+        assert!(!app_version().matches("1.4.2", "1.4.2-rc.1"));
+    }
+
+    #[test]
+    fn versions_are_compared_as_semver_when_they_parse() {
+        assert!(app_version().matches("1.4.0", "v1.4"));
+        assert!(app_version().matches("1.4.2", "1.4.2+build.7"));
+        assert!(!app_version().matches("1.4.2", "1.4.3"));
+        assert!(app_version().matches("main", "main"));
+        assert!(!app_version().matches("main", "develop"));
+    }
+
+    /// `scraper-prod` as deployed to `region` of `account`.
+    fn deployed_to(account: &str, region: &str, version: &str) -> Lambda {
+        Lambda {
+            arn: format!(
+                "arn:aws:lambda:{}:{}:function:scraper-prod",
+                region, account
+            ),
+            account_id: Some(account.to_string()),
+            region: Some(region.to_string()),
+            ..lambda("scraper-prod", Some(version))
+        }
+    }
+
+    #[test]
+    fn each_region_is_compared() {
+        let details = BTreeMap::from([("scraper".to_string(), details(Ok(Some("1.2.0"))))]);
+        let lambdas = vec![
+            deployed_to("123456789012", "eu-west-1", "1.2.0"),
+            deployed_to("123456789012", "us-east-1", "1.1.0"),
+        ];
+
+        let comparisons = compare(&details, &lambdas, &app_version(), &Matcher::default());
+        let regions: Vec<_> = comparisons
+            .iter()
+            .map(|c| (c.lambda.unwrap().region.as_deref().unwrap(), c.status))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                ("eu-west-1", Status::UpToDate),
+                ("us-east-1", Status::Drift)
+            ]
+        );
+        assert_eq!(
+            crate::exit::for_comparisons(&comparisons, None, true),
+            crate::exit::DRIFT
+        );
+    }
+
+    #[test]
+    fn each_account_is_compared() {
+        let details = BTreeMap::from([("scraper".to_string(), details(Ok(Some("1.2.0"))))]);
+        let lambdas = vec![
+            deployed_to("111111111111", "eu-west-1", "1.2.0"),
+            deployed_to("222222222222", "eu-west-1", "1.0.0"),
+        ];
+
+        let comparisons = compare(&details, &lambdas, &app_version(), &Matcher::default());
+        let accounts: Vec<_> = comparisons
+            .iter()
+            .map(|c| {
+                (
+                    c.lambda.unwrap().account_id.as_deref().unwrap(),
+                    c.deployed,
+                    c.status,
+                )
+            })
+            .collect();
+        assert_eq!(
+            accounts,
+            [
+                ("111111111111", Some("1.2.0"), Status::UpToDate),
+                ("222222222222", Some("1.0.0"), Status::Drift)
+            ]
+        );
+    }
+
+    #[test]
+    fn assigns_a_status_per_repo() {
+        let details = BTreeMap::from([
+            ("behind".to_string(), details(Ok(Some("2.0.0")))),
+            ("current".to_string(), details(Ok(Some("1.0.0")))),
+            ("unversioned".to_string(), details(Ok(Some("1.0.0")))),
+            ("missing".to_string(), details(Ok(Some("1.0.0")))),
+            ("broken".to_string(), details(Err("404"))),
+            ("unreleased".to_string(), details(Ok(None))),
+        ]);
+        let lambdas = vec![
+            lambda("behind-prod", Some("1.9.0")),
+            lambda("current-prod", Some("1.0.0")),
+            lambda("unversioned-prod", None),
+        ];
+
+        let matcher = Matcher::default();
+        let statuses: Vec<_> = compare(&details, &lambdas, &app_version(), &matcher)
+            .into_iter()
+            .map(|comparison| (comparison.repo, comparison.status))
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("behind", Status::Drift),
+                ("broken", Status::Error),
+                ("current", Status::UpToDate),
+                ("missing", Status::NoLambda),
+                ("unreleased", Status::MissingVersion),
+                ("unversioned", Status::Unknown),
+            ]
+        );
+    }
+
+    #[test]
+    fn http_source_keeps_the_whole_url() {
+        assert_eq!(
+            "http:https://api.example.com/{function}/version".parse::<VersionSource>(),
+            Ok(VersionSource::Http(
+                "https://api.example.com/{function}/version".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn versions_order_like_semver() {
+        let version = |s: &str| s.parse::<Version>().unwrap();
+        assert!(version("1.9.9") < version("2.0.0"));
+        assert!(version("2.0.0-rc.1") < version("2.0.0"));
+        assert!(version("v2.1") > version("2.0.9"));
+        assert_eq!(version("2.0.0+build.5"), version("2"));
+        assert!("2.0.0.1".parse::<Version>().is_err());
+        assert!("latest".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn the_minimum_is_recorded_beside_the_status() {
+        let compared = |expected: &str| Comparison {
+            expected: Some(expected.to_string()),
+            ..Comparison::new("scraper", Status::Drift)
+        };
+        let mut comparisons = [compared("1.4.0"), compared("2.1.0"), compared("latest")];
+
+        apply_minimum(&mut comparisons, &"2.0.0".parse().unwrap());
+
+        let marked: Vec<_> = comparisons.iter().map(|c| c.below_minimum).collect();
+        assert_eq!(marked, [true, false, false]);
+        assert!(comparisons.iter().all(|c| c.status == Status::Drift));
+    }
+}
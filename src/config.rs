@@ -0,0 +1,371 @@
+//! Settings read from `--config`, or `stuff.toml` when it exists, in TOML or YAML.
+//! Anything given on the command line takes precedence over the file.
+
+use crate::{
+    cli::Args,
+    matching::{MatchKey, MatchRule, MatchStrategy},
+    repos::{self, Repo},
+};
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// Read when no `--config` is given, if it exists in the working directory.
+pub const DEFAULT_PATH: &str = "stuff.toml";
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Owner for repos listed without one.
+    pub owner: Option<String>,
+    /// Repos to scan instead of the defaults.
+    pub repos: Vec<RepoEntry>,
+    pub region: Option<String>,
+    /// Several regions to list functions in, instead of `region`.
+    pub regions: Vec<String>,
+    pub match_strategy: Option<MatchStrategy>,
+    pub match_key: Option<MatchKey>,
+    pub stages: Option<Vec<String>>,
+    pub match_tag: Option<String>,
+    pub strip_scope: Option<bool>,
+    pub discover_packages: Option<bool>,
+    pub function_name_template: Option<String>,
+    pub fuzzy_threshold: Option<f64>,
+    pub version_env_var: Option<String>,
+}
+
+/// A repo as `repo` or `owner/repo`, or as a table that also picks its branch.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum RepoEntry {
+    Name(String),
+    Table(RepoTable),
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RepoTable {
+    pub repo: String,
+    /// Branch, tag, or SHA to read the repo at, instead of its default branch.
+    pub branch: Option<String>,
+    /// The repo's function, by exact name, instead of matching one.
+    pub function: Option<String>,
+    /// A regex the repo's function name matches, instead of the usual matching.
+    pub function_pattern: Option<String>,
+    /// Directories of a monorepo's package.json files, e.g. `packages/api`, each
+    /// compared as its own service.
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+impl RepoTable {
+    fn rule(&self) -> Result<Option<MatchRule>> {
+        if !self.packages.is_empty() && (self.function.is_some() || self.function_pattern.is_some())
+        {
+            return Err(anyhow!(
+                "{} lists packages, which are matched to functions one by one, so it can't set function or function-pattern",
+                self.repo
+            ));
+        }
+        match (&self.function, &self.function_pattern) {
+            (Some(_), Some(_)) => Err(anyhow!(
+                "{} sets both function and function-pattern",
+                self.repo
+            )),
+            (Some(name), None) => Ok(Some(MatchRule::Name(name.clone()))),
+            (None, Some(pattern)) => Regex::new(pattern)
+                .map(|pattern| Some(MatchRule::Pattern(pattern)))
+                .map_err(|e| anyhow!("invalid function-pattern for {}: {}", self.repo, e)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl RepoEntry {
+    fn name(&self) -> &str {
+        match self {
+            RepoEntry::Name(name) | RepoEntry::Table(RepoTable { repo: name, .. }) => name,
+        }
+    }
+
+    /// The repo, or with `packages`, one entry per package.
+    fn parse(&self, default_owner: &str) -> Result<Vec<Repo>> {
+        let mut repo = Repo::parse(self.name(), default_owner)?;
+        match self {
+            RepoEntry::Table(table) => {
+                repo.git_ref = table.branch.clone();
+                if table.packages.is_empty() {
+                    Ok(vec![repo])
+                } else {
+                    Ok(repo.with_packages(&table.packages))
+                }
+            }
+            RepoEntry::Name(_) => Ok(vec![repo]),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path` as YAML when it ends in `.yaml` or `.yml`, and as TOML when
+    /// it ends in `.toml`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let (config, content) = Self::read(path)?;
+        match config.problems(&content).into_iter().next() {
+            Some(problem) => Err(anyhow!("Invalid config {}: {}", path.display(), problem)),
+            None => Ok(config),
+        }
+    }
+
+    /// The parsers' errors already say which line they're on.
+    fn read(path: &Path) -> Result<(Self, String)> {
+        read_file(path, "config")
+    }
+
+    /// Everything wrong with the values, located in `content` where possible.
+    fn problems(&self, content: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(threshold) = self.fuzzy_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                problems.push(at_line(
+                    content,
+                    "fuzzy-threshold",
+                    format!(
+                        "fuzzy-threshold must be between 0.0 and 1.0, got {}",
+                        threshold
+                    ),
+                ));
+            }
+        }
+
+        if self.region.is_some() && !self.regions.is_empty() {
+            problems.push(at_line(
+                content,
+                "regions",
+                "set either region or regions, not both".to_string(),
+            ));
+        }
+
+        let owner = self.owner.as_deref().unwrap_or(repos::DEFAULT_OWNER);
+        let mut seen = HashSet::new();
+        for entry in &self.repos {
+            let name = entry.name();
+            match entry.parse(owner) {
+                Ok(parsed) => {
+                    for repo in parsed.iter().filter(|repo| !seen.insert(repo.to_string())) {
+                        problems.push(at_line(content, name, format!("{} is listed twice", repo)));
+                    }
+                }
+                Err(e) => problems.push(at_line(content, name, e.to_string())),
+            }
+            if let RepoEntry::Table(table) = entry {
+                if let Err(e) = table.rule() {
+                    problems.push(at_line(content, name, e.to_string()));
+                }
+            }
+        }
+
+        problems
+    }
+
+    fn repo_list(&self) -> Result<Vec<Repo>> {
+        let owner = self.owner.as_deref().unwrap_or(repos::DEFAULT_OWNER);
+        let repos: Vec<Vec<Repo>> = self
+            .repos
+            .iter()
+            .map(|entry| entry.parse(owner))
+            .collect::<Result<_>>()?;
+        Ok(repos.into_iter().flatten().collect())
+    }
+
+    /// The `function` and `function-pattern` rules, keyed by repo name.
+    fn match_rules(&self) -> Result<BTreeMap<String, MatchRule>> {
+        let owner = self.owner.as_deref().unwrap_or(repos::DEFAULT_OWNER);
+        let mut rules = BTreeMap::new();
+        for entry in &self.repos {
+            if let RepoEntry::Table(table) = entry {
+                if let Some(rule) = table.rule()? {
+                    let name = Repo::parse(entry.name(), owner)?.name;
+                    rules.insert(name, rule);
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// The `validate` subcommand: every problem with the file at `path`, and with
+    /// how `args` refers to what it lists. Makes no network calls.
+    pub fn check(path: &Path, args: &Args) -> Result<Vec<String>> {
+        let (config, content) = Self::read(path)?;
+        let mut problems = config.problems(&content);
+
+        if !config.repos.is_empty() {
+            let listed = config.repo_list().unwrap_or_default();
+            for (flag, names) in [
+                ("--only-repos", &args.only_repos),
+                ("--skip-repos", &args.skip_repos),
+            ] {
+                for name in names {
+                    if !listed.iter().any(|repo| repo.is_named(name)) {
+                        problems.push(format!("{} names {}, which isn't in repos", flag, name));
+                    }
+                }
+            }
+        }
+
+        if let Some(template) = &args.function_name_template {
+            let placeholder = Regex::new(r"\{(\w+)\}").unwrap();
+            for captures in placeholder.captures_iter(template) {
+                if !["repo", "name"].contains(&&captures[1]) {
+                    problems.push(format!(
+                        "--function-name-template has unknown placeholder {{{}}}, expected {{repo}} or {{name}}",
+                        &captures[1]
+                    ));
+                }
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Fills in `args` from the file wherever `matches` shows the value wasn't
+    /// given on the command line.
+    pub fn apply(mut self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if from_cli("owner") {
+            self.owner = Some(args.owner.clone());
+        } else if let Some(owner) = &self.owner {
+            args.owner = owner.clone();
+        }
+        args.config_repos = self.repo_list()?;
+        args.match_rules = self.match_rules()?;
+        // `--region` and `--regions` both override either setting.
+        if !from_cli("region") && !from_cli("regions") {
+            if self.region.is_some() {
+                args.region = self.region;
+            }
+            if !self.regions.is_empty() {
+                args.regions = self.regions;
+            }
+        }
+        if let (false, Some(strategy)) = (from_cli("match_strategy"), self.match_strategy) {
+            args.match_strategy = strategy;
+        }
+        if let (false, Some(key)) = (from_cli("match_key"), self.match_key) {
+            args.match_key = key;
+        }
+        if let (false, Some(stages)) = (from_cli("stages"), self.stages) {
+            args.stages = stages;
+        }
+        if let (false, Some(tag)) = (from_cli("match_tag"), self.match_tag) {
+            args.match_tag = tag;
+        }
+        if let (false, Some(strip_scope)) = (from_cli("strip_scope"), self.strip_scope) {
+            args.strip_scope = strip_scope;
+        }
+        if let (false, Some(discover)) = (from_cli("discover_packages"), self.discover_packages) {
+            args.discover_packages = discover;
+        }
+        if !from_cli("function_name_template") && self.function_name_template.is_some() {
+            args.function_name_template = self.function_name_template;
+        }
+        if let (false, Some(threshold)) = (from_cli("fuzzy_threshold"), self.fuzzy_threshold) {
+            args.fuzzy_threshold = threshold;
+        }
+        if let (false, Some(key)) = (from_cli("version_env_var"), self.version_env_var) {
+            args.version_env_var = key;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads `path` as TOML or YAML by its extension, along with its text. `what`
+/// names the file in errors, e.g. `config`.
+pub fn read_file<T: DeserializeOwned>(path: &Path, what: &str) -> Result<(T, String)> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {} {}", what, path.display()))?;
+
+    // Windows file names are case-insensitive, so `STUFF.TOML` is TOML too.
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let value = match extension.as_deref() {
+        Some("toml") => toml::from_str(&content).map_err(|e| anyhow!("{}", e)),
+        Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(|e| anyhow!("{}", e)),
+        _ => Err(anyhow!("expected a .toml, .yaml, or .yml file")),
+    }
+    .with_context(|| format!("Invalid {} {}", what, path.display()))?;
+
+    Ok((value, content))
+}
+
+/// Prefixes `problem` with the first line of `content` mentioning `needle`.
+fn at_line(content: &str, needle: &str, problem: String) -> String {
+    match content.lines().position(|line| line.contains(needle)) {
+        Some(index) => format!("line {}: {}", index + 1, problem),
+        None => problem,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_yaml_read_into_the_same_config() {
+        let toml = r#"
+            owner = "acme"
+            repos = ["payments", { repo = "Mikeysauce/scraper", branch = "develop" }]
+            match-strategy = "fuzzy"
+            fuzzy-threshold = 0.9
+        "#;
+        let yaml = "
+owner: acme
+repos: [payments, { repo: Mikeysauce/scraper, branch: develop }]
+match-strategy: fuzzy
+fuzzy-threshold: 0.9
+";
+
+        let from_toml: Config = toml::from_str(toml).unwrap();
+        let from_yaml: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(from_toml, from_yaml);
+
+        let repos: Vec<String> = from_toml
+            .repo_list()
+            .unwrap()
+            .iter()
+            .map(Repo::to_string)
+            .collect();
+        assert_eq!(repos, vec!["acme/payments", "Mikeysauce/scraper"]);
+        let branches: Vec<Option<String>> = from_toml
+            .repo_list()
+            .unwrap()
+            .into_iter()
+            .map(|repo| repo.git_ref)
+            .collect();
+        assert_eq!(branches, vec![None, Some("develop".to_string())]);
+    }
+
+    #[test]
+    fn problems_are_located_by_line() {
+        let content = "owner = \"acme\"\nrepos = [\n  \"payments\",\n  \"acme/payments\",\n]\nfuzzy-threshold = 1.5\n";
+        let config: Config = toml::from_str(content).unwrap();
+
+        assert_eq!(
+            config.problems(content),
+            vec![
+                "line 6: fuzzy-threshold must be between 0.0 and 1.0, got 1.5",
+                "line 4: acme/payments is listed twice",
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Checks that a shared dependency is pinned to the same version in every repo.
+
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+};
+
+/// The version range a package.json declares for `name`, from `dependencies`
+/// or, failing that, `devDependencies`.
+pub fn dependency_version(package_json: &HashMap<String, Value>, name: &str) -> Option<String> {
+    ["dependencies", "devDependencies"]
+        .iter()
+        .filter_map(|section| package_json.get(*section))
+        .find_map(|deps| deps.get(name))
+        .and_then(|version| version.as_str())
+        .map(str::to_string)
+}
+
+/// The version every repo is measured against: `expected` when given, otherwise
+/// the most common declared version (ties go to the lowest string, to stay stable).
+fn baseline<'a>(
+    versions: &'a BTreeMap<String, Option<String>>,
+    expected: Option<&'a str>,
+) -> Option<&'a str> {
+    if expected.is_some() {
+        return expected;
+    }
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for version in versions.values().flatten() {
+        *counts.entry(version).or_default() += 1;
+    }
+
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(version, _)| version)
+}
+
+/// Repos whose declared version differs from the baseline. Repos that don't
+/// depend on the package at all are not counted as diverging.
+pub fn diverging<'a>(
+    versions: &'a BTreeMap<String, Option<String>>,
+    expected: Option<&str>,
+) -> Vec<&'a str> {
+    let Some(baseline) = baseline(versions, expected) else {
+        return Vec::new();
+    };
+
+    versions
+        .iter()
+        .filter(|(_, version)| matches!(version, Some(v) if v != baseline))
+        .map(|(repo, _)| repo.as_str())
+        .collect()
+}
+
+pub fn write_dependency_report(
+    out: &mut impl Write,
+    name: &str,
+    versions: &BTreeMap<String, Option<String>>,
+    expected: Option<&str>,
+) -> io::Result<()> {
+    let baseline = baseline(versions, expected);
+    let diverging = diverging(versions, expected);
+
+    writeln!(out, "Dependency {}:", name)?;
+    for (repo, version) in versions {
+        match version {
+            Some(version) if diverging.contains(&repo.as_str()) => writeln!(
+                out,
+                "  {}: {} (expected {})",
+                repo,
+                version,
+                baseline.unwrap_or_default()
+            )?,
+            Some(version) => writeln!(out, "  {}: {}", repo, version)?,
+            None => writeln!(out, "  {}: not a dependency", repo)?,
+        }
+    }
+
+    match baseline {
+        Some(baseline) => writeln!(out, "{} repo(s) diverge from {}", diverging.len(), baseline),
+        None => writeln!(out, "No repo depends on {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(entries: &[(&str, Option<&str>)]) -> BTreeMap<String, Option<String>> {
+        entries
+            .iter()
+            .map(|(repo, version)| (repo.to_string(), version.map(str::to_string)))
+            .collect()
+    }
+
+    #[test]
+    fn majority_version_is_the_baseline() {
+        let versions = versions(&[
+            ("a", Some("^1.2.0")),
+            ("b", Some("^1.2.0")),
+            ("c", Some("^1.3.0")),
+            ("d", None),
+        ]);
+
+        assert_eq!(diverging(&versions, None), vec!["c"]);
+        assert_eq!(diverging(&versions, Some("^1.3.0")), vec!["a", "b"]);
+    }
+}
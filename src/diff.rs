@@ -0,0 +1,194 @@
+//! What changed since a previous run, read from its `--format json` or
+//! `--format ndjson` output.
+
+use crate::compare::{Comparison, Status};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// The parts of a previous run's result line that are diffed.
+#[derive(Debug, Deserialize)]
+pub struct PreviousResult {
+    pub repo: String,
+    pub deployed: Option<String>,
+    pub status: Status,
+}
+
+/// Reads a previous run's results, keyed by repo: a `--format json` array, or
+/// one result per line as `--format ndjson` writes them.
+pub fn load_previous(path: &Path) -> Result<BTreeMap<String, PreviousResult>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read previous run {}: {}", path.display(), e))?;
+    parse_previous(&content, path)
+}
+
+fn parse_previous(content: &str, path: &Path) -> Result<BTreeMap<String, PreviousResult>> {
+    if content.trim_start().starts_with('[') {
+        let results: Vec<PreviousResult> = serde_json::from_str(content)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        return Ok(results
+            .into_iter()
+            .map(|result| (result.repo.clone(), result))
+            .collect());
+    }
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let result: PreviousResult = serde_json::from_str(line).map_err(|e| {
+                anyhow!(
+                    "Failed to parse line {} of {}: {}",
+                    index + 1,
+                    path.display(),
+                    e
+                )
+            })?;
+            Ok((result.repo.clone(), result))
+        })
+        .collect()
+}
+
+pub fn write_diff(
+    out: &mut impl Write,
+    previous: &BTreeMap<String, PreviousResult>,
+    comparisons: &[Comparison],
+) -> io::Result<()> {
+    let mut changes = Vec::new();
+
+    for comparison in comparisons {
+        let Some(before) = previous.get(comparison.repo) else {
+            changes.push(format!(
+                "{}: new, {}",
+                comparison.repo,
+                describe(comparison.status)
+            ));
+            continue;
+        };
+
+        if before.deployed.as_deref() != comparison.deployed {
+            changes.push(format!(
+                "{}: deployed {} -> {}",
+                comparison.repo,
+                before.deployed.as_deref().unwrap_or("none"),
+                comparison.deployed.unwrap_or("none")
+            ));
+        }
+        match (before.status, comparison.status) {
+            (before, now) if before == now => {}
+            (_, Status::Drift) => changes.push(format!("{}: drift appeared", comparison.repo)),
+            (Status::Drift, Status::UpToDate) => {
+                changes.push(format!("{}: drift resolved", comparison.repo))
+            }
+            (before, now) => changes.push(format!(
+                "{}: {} -> {}",
+                comparison.repo,
+                describe(before),
+                describe(now)
+            )),
+        }
+    }
+
+    for repo in previous.keys() {
+        if !comparisons.iter().any(|comparison| comparison.repo == repo) {
+            changes.push(format!("{}: no longer scanned", repo));
+        }
+    }
+
+    writeln!(out, "Changes since the previous run ({}):", changes.len())?;
+    for change in changes {
+        writeln!(out, "  {}", change)?;
+    }
+
+    Ok(())
+}
+
+fn describe(status: Status) -> String {
+    status.heading().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn previous(repo: &str, deployed: Option<&str>, status: Status) -> (String, PreviousResult) {
+        let result = PreviousResult {
+            repo: repo.to_string(),
+            deployed: deployed.map(str::to_string),
+            status,
+        };
+        (repo.to_string(), result)
+    }
+
+    fn comparison<'a>(repo: &'a str, deployed: Option<&'a str>, status: Status) -> Comparison<'a> {
+        Comparison {
+            deployed,
+            ..Comparison::new(repo, status)
+        }
+    }
+
+    #[test]
+    fn reads_ndjson_lines() {
+        let content = r#"{"repo": "scraper", "deployed": "1.0.0", "status": "drift"}
+
+{"repo": "movies-front", "deployed": null, "status": "no_lambda"}
+"#;
+        let previous = parse_previous(content, Path::new("previous.ndjson")).unwrap();
+        assert_eq!(previous["scraper"].deployed.as_deref(), Some("1.0.0"));
+        assert_eq!(previous["movies-front"].status, Status::NoLambda);
+    }
+
+    #[test]
+    fn reads_a_json_array() {
+        let content = r#"[
+  {
+    "repo": "scraper",
+    "deployed": "1.0.0",
+    "status": "drift"
+  },
+  {
+    "repo": "movies-front",
+    "deployed": null,
+    "status": "up_to_date"
+  }
+]
+"#;
+        let previous = parse_previous(content, Path::new("previous.json")).unwrap();
+        assert_eq!(previous.len(), 2);
+        assert_eq!(previous["scraper"].status, Status::Drift);
+        assert_eq!(previous["movies-front"].deployed, None);
+    }
+
+    #[test]
+    fn reports_version_moves_and_status_changes() {
+        let previous = BTreeMap::from([
+            previous("scraper", Some("1.0.0"), Status::Drift),
+            previous("movies-front", Some("2.0.0"), Status::UpToDate),
+            previous("retired", None, Status::NoLambda),
+        ]);
+        let comparisons = vec![
+            comparison("scraper", Some("1.2.0"), Status::UpToDate),
+            comparison("movies-front", Some("2.0.0"), Status::Drift),
+            comparison("standen-node", None, Status::NoLambda),
+        ];
+
+        let mut out = Vec::new();
+        write_diff(&mut out, &previous, &comparisons).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Changes since the previous run (5):\n\
+             \x20 scraper: deployed 1.0.0 -> 1.2.0\n\
+             \x20 scraper: drift resolved\n\
+             \x20 movies-front: drift appeared\n\
+             \x20 standen-node: new, no lambda found\n\
+             \x20 retired: no longer scanned\n"
+        );
+    }
+}
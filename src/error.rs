@@ -0,0 +1,39 @@
+//! Why a repo's expected version couldn't be read, with enough context to say
+//! where the tool looked.
+
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Clone, Debug, Error, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FetchError {
+    /// Fine-grained tokens get a 404 for every repo outside their scope.
+    #[error("token cannot access {owner}/{repo}")]
+    NoAccess { owner: String, repo: String },
+
+    #[error("failed to read {path} in {owner}/{repo}{}: {message}", at_ref(.git_ref))]
+    File {
+        owner: String,
+        repo: String,
+        /// `None` for the default branch.
+        #[serde(rename = "ref")]
+        git_ref: Option<String>,
+        path: String,
+        message: String,
+    },
+
+    #[error("failed to read release asset {asset} in {owner}/{repo}: {message}")]
+    ReleaseAsset {
+        owner: String,
+        repo: String,
+        asset: String,
+        message: String,
+    },
+}
+
+fn at_ref(git_ref: &Option<String>) -> String {
+    git_ref
+        .as_ref()
+        .map(|git_ref| format!(" at {}", git_ref))
+        .unwrap_or_default()
+}
@@ -0,0 +1,130 @@
+//! Process exit codes, so CI can tell "a service is behind" apart from "the
+//! tool couldn't talk to GitHub or AWS".
+
+use crate::{
+    cli::Severity,
+    compare::{Comparison, Status},
+};
+use std::fmt;
+
+pub const SUCCESS: i32 = 0;
+pub const DRIFT: i32 = 1;
+pub const REPO_FETCH_FAILED: i32 = 2;
+/// Bad arguments or config, missing credentials, or output that couldn't be written.
+pub const FAILED: i32 = 3;
+pub const AWS_LISTING_FAILED: i32 = 4;
+pub const AUTH_FAILED: i32 = 5;
+
+/// Shown at the end of `--help`.
+pub const HELP: &str = "\
+Exit codes (the highest applicable one wins):
+  0    every compared function is up to date
+  1    drift detected, a repo is below --min-version, a repo's
+       dependencies violate --dependency-policy, or a repo has no
+       function (unless --allow-missing-function)
+  2    some repos could not be fetched, or lack a version with
+       --warn-missing-version error
+  3    invalid arguments or config, or no credentials to start with
+  4    listing Lambda functions failed
+  5    GitHub or AWS rejected the credentials
+  130  interrupted; the printed results are partial";
+
+/// GitHub or AWS rejected the credentials we sent.
+#[derive(Debug)]
+pub struct AuthError(pub String);
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "authentication failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// The code for a run that failed outright with `e`, where `fallback` is the
+/// code for the step that failed.
+pub fn for_error(e: &anyhow::Error, fallback: i32) -> i32 {
+    if e.downcast_ref::<AuthError>().is_some() {
+        AUTH_FAILED
+    } else {
+        fallback
+    }
+}
+
+/// `missing_version` is how seriously to take a repo without a version, and
+/// `missing_function` whether a repo without a function is drift.
+pub fn for_comparisons(
+    comparisons: &[Comparison],
+    missing_version: Option<Severity>,
+    missing_function: bool,
+) -> i32 {
+    comparisons
+        .iter()
+        .map(|comparison| {
+            let code = match comparison.status {
+                Status::Drift => DRIFT,
+                Status::NoLambda if missing_function => DRIFT,
+                Status::Error => REPO_FETCH_FAILED,
+                Status::MissingVersion if missing_version == Some(Severity::Error) => {
+                    REPO_FETCH_FAILED
+                }
+                _ => SUCCESS,
+            };
+            if comparison.below_minimum {
+                code.max(DRIFT)
+            } else {
+                code
+            }
+        })
+        .max()
+        .unwrap_or(SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(status: Status) -> Comparison<'static> {
+        Comparison::new("scraper", status)
+    }
+
+    #[test]
+    fn the_most_serious_status_decides_the_code() {
+        let up_to_date = [comparison(Status::UpToDate), comparison(Status::Unknown)];
+        assert_eq!(for_comparisons(&up_to_date, None, true), SUCCESS);
+
+        let missing = [comparison(Status::UpToDate), comparison(Status::NoLambda)];
+        assert_eq!(for_comparisons(&missing, None, true), DRIFT);
+        assert_eq!(for_comparisons(&missing, None, false), SUCCESS);
+
+        let unversioned = [comparison(Status::MissingVersion)];
+        assert_eq!(
+            for_comparisons(&unversioned, Some(Severity::Warn), true),
+            SUCCESS
+        );
+        assert_eq!(
+            for_comparisons(&unversioned, Some(Severity::Error), true),
+            REPO_FETCH_FAILED
+        );
+
+        let below_minimum = [Comparison {
+            below_minimum: true,
+            ..comparison(Status::UpToDate)
+        }];
+        assert_eq!(for_comparisons(&below_minimum, None, true), DRIFT);
+
+        let mixed = [comparison(Status::Drift), comparison(Status::Error)];
+        assert_eq!(for_comparisons(&mixed, None, true), REPO_FETCH_FAILED);
+    }
+
+    #[test]
+    fn rejected_credentials_win_over_the_failing_step() {
+        let rejected = anyhow::Error::new(AuthError("bad token".to_string()))
+            .context("Failed to list the repos");
+        assert_eq!(for_error(&rejected, AWS_LISTING_FAILED), AUTH_FAILED);
+        assert_eq!(
+            for_error(&anyhow::anyhow!("throttled"), AWS_LISTING_FAILED),
+            AWS_LISTING_FAILED
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Loads package.json contents from disk instead of GitHub.
+//!
+//! Each `<repo>.json` file in the fixture directory stands in for that repo's
+//! package.json, so the comparison can be exercised without a GitHub token.
+
+use crate::RepoDetails;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+pub fn load_packagejson_details(dir: &Path) -> Result<BTreeMap<String, RepoDetails>> {
+    let mut package_json_details: BTreeMap<String, RepoDetails> = BTreeMap::new();
+
+    for (repo, path) in fixture_files(dir)? {
+        let started = Instant::now();
+        let package_json = read_fixture(&path)?;
+
+        let details = RepoDetails {
+            version: Ok(package_json.get("version").cloned()),
+            package_name: package_name(&package_json),
+            commit: None,
+            elapsed: started.elapsed(),
+        };
+        package_json_details.insert(repo, details);
+    }
+
+    Ok(package_json_details)
+}
+
+fn package_name(package_json: &HashMap<String, Value>) -> Option<String> {
+    package_json.get("name")?.as_str().map(str::to_string)
+}
+
+pub fn load_packagejsons(dir: &Path) -> Result<BTreeMap<String, HashMap<String, Value>>> {
+    fixture_files(dir)?
+        .into_iter()
+        .map(|(repo, path)| Ok((repo, read_fixture(&path)?)))
+        .collect()
+}
+
+/// The `(repo, path)` of every `.json` file in `dir`.
+fn fixture_files(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| anyhow!("Failed to read fixture directory {}: {}", dir.display(), e))?;
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let repo = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("Invalid fixture file name: {}", path.display()))?
+            .to_string();
+
+        files.push((repo, path));
+    }
+
+    Ok(files)
+}
+
+fn read_fixture(path: &Path) -> Result<HashMap<String, Value>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read fixture {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse fixture {}: {}", path.display(), e))
+}
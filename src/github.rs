@@ -0,0 +1,99 @@
+//! The GitHub requests repo files are read with, behind [`GitHubApi`] so tests
+//! can answer them without a token.
+
+use crate::auth::GitHubAuth;
+use http::{
+    header::{HeaderMap, HeaderValue, ACCEPT, ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+use octocrab::Octocrab;
+use std::future::Future;
+
+pub trait GitHubApi: Sync {
+    /// GETs an API route, e.g. `/repos/OWNER/REPO`. With `etag`, GitHub may
+    /// answer that nothing changed instead of resending the body.
+    fn get(
+        &self,
+        route: &str,
+        etag: Option<&str>,
+    ) -> impl Future<Output = Result<Fetched, ApiError>> + Send;
+
+    /// Downloads a release asset from its API URL.
+    fn download_asset(&self, url: &str) -> impl Future<Output = Result<String, ApiError>> + Send;
+}
+
+pub enum Fetched {
+    NotModified,
+    Body { etag: Option<String>, body: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// GitHub answered with an error, e.g. `Not Found` or `Bad credentials`.
+    #[error("{message}")]
+    GitHub { message: String },
+    #[error("HTTP {0}")]
+    Status(StatusCode),
+    #[error(transparent)]
+    Request(octocrab::Error),
+}
+
+impl From<octocrab::Error> for ApiError {
+    fn from(e: octocrab::Error) -> Self {
+        match e {
+            octocrab::Error::GitHub { source, .. } => ApiError::GitHub {
+                message: source.message,
+            },
+            e => ApiError::Request(e),
+        }
+    }
+}
+
+impl GitHubApi for Octocrab {
+    async fn get(&self, route: &str, etag: Option<&str>) -> Result<Fetched, ApiError> {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = etag.and_then(|etag| etag.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+
+        let response = self._get_with_headers(route, Some(headers)).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+
+        let response = octocrab::map_github_error(response).await?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let body = self.body_to_string(response).await?;
+        Ok(Fetched::Body { etag, body })
+    }
+
+    async fn download_asset(&self, url: &str) -> Result<String, ApiError> {
+        // The asset API answers with the raw bytes, or a redirect to them, only when
+        // asked for octet-stream; otherwise it returns the asset's metadata.
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/octet-stream"));
+        let response = self._get_with_headers(url, Some(headers)).await?;
+        let response = self.follow_location_to_data(response).await?;
+        if !response.status().is_success() {
+            return Err(ApiError::Status(response.status()));
+        }
+        Ok(self.body_to_string(response).await?)
+    }
+}
+
+/// Each request takes a client from [`GitHubAuth::client`], so a long run picks
+/// up refreshed installation tokens.
+impl GitHubApi for GitHubAuth {
+    async fn get(&self, route: &str, etag: Option<&str>) -> Result<Fetched, ApiError> {
+        // `Octocrab` has an inherent `get` of its own.
+        GitHubApi::get(&*self.client().await?, route, etag).await
+    }
+
+    async fn download_asset(&self, url: &str) -> Result<String, ApiError> {
+        self.client().await?.download_asset(url).await
+    }
+}
@@ -0,0 +1,148 @@
+//! The `report` subcommand: flags functions on outdated Node runtimes or left
+//! unmodified for too long.
+
+use crate::Lambda;
+use std::{
+    io::{self, Write},
+    time::SystemTime,
+};
+
+pub struct Rules {
+    /// Node runtimes below this major version are flagged.
+    pub min_node_major: u32,
+    /// Functions modified longer ago than this are flagged.
+    pub stale_days: Option<i64>,
+}
+
+/// Everything `rules` flags about `fnc`, given today's date in days since the epoch.
+fn problems(fnc: &Lambda, rules: &Rules, today: i64) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if let Some(runtime) = &fnc.runtime {
+        if node_major(runtime).is_some_and(|major| major < rules.min_node_major) {
+            problems.push(format!(
+                "runtime {} is older than Node {}",
+                runtime, rules.min_node_major
+            ));
+        }
+    }
+
+    let modified = fnc.last_modified.as_deref().and_then(parse_date);
+    if let (Some(stale_days), Some(modified)) = (rules.stale_days, modified) {
+        let age = today - modified;
+        if age > stale_days {
+            problems.push(format!("last modified {} days ago", age));
+        }
+    }
+
+    problems
+}
+
+/// `nodejs20.x` is 20. The bare `nodejs` runtime was Node 0.10.
+fn node_major(runtime: &str) -> Option<u32> {
+    let version = runtime.strip_prefix("nodejs")?;
+    if version.is_empty() {
+        return Some(0);
+    }
+    version.split('.').next()?.parse().ok()
+}
+
+/// Days since the epoch for the `YYYY-MM-DD` a timestamp starts with.
+pub fn parse_date(timestamp: &str) -> Option<i64> {
+    let mut parts = timestamp
+        .get(..10)?
+        .split('-')
+        .map(|part| part.parse::<i64>());
+    let (year, month, day) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Howard Hinnant's days_from_civil.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+pub fn days_since_epoch(now: SystemTime) -> i64 {
+    let seconds = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (seconds / 86_400) as i64
+}
+
+/// Writes what's flagged for each function, returning how many were flagged.
+pub fn write_report(
+    out: &mut impl Write,
+    lambdas: &[Lambda],
+    rules: &Rules,
+    today: i64,
+) -> io::Result<usize> {
+    writeln!(out, "Fleet health ({} functions):", lambdas.len())?;
+    let mut flagged = 0;
+    for fnc in lambdas {
+        let problems = problems(fnc, rules, today);
+        if problems.is_empty() {
+            continue;
+        }
+        flagged += 1;
+        writeln!(out, "  {}: {}", fnc.name, problems.join("; "))?;
+    }
+    writeln!(out, "{} function(s) flagged", flagged)?;
+
+    Ok(flagged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lambda(name: &str, runtime: &str, last_modified: &str) -> Lambda {
+        Lambda {
+            name: name.to_string(),
+            runtime: Some(runtime.to_string()),
+            last_modified: Some(last_modified.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags_old_node_runtimes_and_stale_functions() {
+        let rules = Rules {
+            min_node_major: 20,
+            stale_days: Some(365),
+        };
+        let lambdas = vec![
+            lambda("scraper-prod", "nodejs22.x", "2026-09-01T10:00:00.000+0000"),
+            lambda(
+                "movies-front-prod",
+                "nodejs16.x",
+                "2024-01-01T10:00:00.000+0000",
+            ),
+            lambda(
+                "standen-node-prod",
+                "python3.12",
+                "2026-09-01T10:00:00.000+0000",
+            ),
+        ];
+        let today = parse_date("2026-10-14").unwrap();
+
+        let mut out = Vec::new();
+        let flagged = write_report(&mut out, &lambdas, &rules, today).unwrap();
+
+        assert_eq!(flagged, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Fleet health (3 functions):\n\
+             \x20 movies-front-prod: runtime nodejs16.x is older than Node 20; last modified 1017 days ago\n\
+             1 function(s) flagged\n"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Shell commands that receive results as JSON on stdin, for notifications or
+//! ticketing the tool doesn't integrate itself.
+
+use crate::compare::Comparison;
+use serde::Serialize;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Runs `command` once per matched function with that comparison as JSON.
+pub fn on_result(command: &str, comparisons: &[Comparison]) {
+    for comparison in comparisons.iter().filter(|c| c.lambda.is_some()) {
+        run(command, comparison);
+    }
+}
+
+/// Runs `command` once with every comparison as a JSON array.
+pub fn on_complete(command: &str, comparisons: &[Comparison]) {
+    run(command, comparisons);
+}
+
+/// Failures are only warned about, so a broken hook never hides the report.
+fn run<T: Serialize + ?Sized>(command: &str, payload: &T) {
+    let payload = match serde_json::to_vec(payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warning!("failed to serialize hook payload: {}", e);
+            return;
+        }
+    };
+
+    let child = shell(command).stdin(Stdio::piped()).spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warning!("failed to run hook `{}`: {}", command, e);
+            return;
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that exits without reading its input is not an error worth reporting.
+        let _ = stdin.write_all(&payload);
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warning!("hook `{}` exited with {}", command, status),
+        Err(e) => warning!("failed to wait for hook `{}`: {}", command, e),
+    }
+}
+
+/// `sh -c`, or `cmd /C` on Windows, where there's usually no `sh`.
+fn shell(command: &str) -> Command {
+    let (program, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut shell = Command::new(program);
+    shell.arg(flag).arg(command);
+    shell
+}
@@ -0,0 +1,212 @@
+//! `--format junit`: one test case per repo, so CI test-result views show drift
+//! next to unit test failures.
+
+use crate::compare::{Comparison, Status};
+use std::io::{self, Write};
+
+/// How one repo's test case ends.
+enum Outcome {
+    Passed,
+    /// A message and a failure type.
+    Failure(String, &'static str),
+    Error(String),
+    Skipped(&'static str),
+}
+
+fn outcome(comparison: &Comparison, expected_label: &str, missing_function: bool) -> Outcome {
+    let expected = comparison.expected.as_deref().unwrap_or("none");
+    let deployed = comparison.deployed.unwrap_or("unknown");
+    let below_minimum = format!(
+        "{} {} is below the minimum version",
+        expected_label, expected
+    );
+    match comparison.status {
+        Status::Error => Outcome::Error(
+            comparison
+                .error
+                .map_or_else(|| "fetch failed".to_string(), |e| e.to_string()),
+        ),
+        Status::Drift => {
+            let mut message = format!("{} {}, deployed {}", expected_label, expected, deployed);
+            if comparison.below_minimum {
+                message = format!("{}; {}", message, below_minimum);
+            }
+            Outcome::Failure(message, "drift")
+        }
+        _ if comparison.below_minimum => Outcome::Failure(below_minimum, "min_version"),
+        Status::NoLambda if missing_function => {
+            Outcome::Failure(Status::NoLambda.heading().to_string(), "missing_function")
+        }
+        Status::UpToDate => Outcome::Passed,
+        status => Outcome::Skipped(status.heading()),
+    }
+}
+
+/// `missing_function` is whether a repo without a function fails, as it does for
+/// the exit code, rather than being skipped.
+pub fn write_junit(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    expected_label: &str,
+    missing_function: bool,
+) -> io::Result<()> {
+    let outcomes: Vec<Outcome> = comparisons
+        .iter()
+        .map(|comparison| outcome(comparison, expected_label, missing_function))
+        .collect();
+    let count = |counted: fn(&Outcome) -> bool| outcomes.iter().filter(|o| counted(o)).count();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuite name="beacon2" tests="{}" failures="{}" errors="{}" skipped="{}">"#,
+        comparisons.len(),
+        count(|o| matches!(o, Outcome::Failure(..))),
+        count(|o| matches!(o, Outcome::Error(_))),
+        count(|o| matches!(o, Outcome::Skipped(_))),
+    )?;
+
+    for (comparison, outcome) in comparisons.iter().zip(&outcomes) {
+        let name = match comparison.lambda {
+            Some(fnc) => format!("{} -> {}", comparison.repo, fnc.name),
+            None => comparison.repo.to_string(),
+        };
+        write!(
+            out,
+            r#"  <testcase classname="beacon2" name="{}""#,
+            escape(&name)
+        )?;
+
+        let body = match outcome {
+            Outcome::Passed => {
+                writeln!(out, "/>")?;
+                continue;
+            }
+            Outcome::Failure(message, kind) => format!(
+                r#"<failure message="{}" type="{}"/>"#,
+                escape(message),
+                kind
+            ),
+            Outcome::Error(message) => {
+                format!(r#"<error message="{}" type="fetch"/>"#, escape(message))
+            }
+            Outcome::Skipped(message) => format!(r#"<skipped message="{}"/>"#, escape(message)),
+        };
+        writeln!(out, ">")?;
+        writeln!(out, "    {}", body)?;
+        writeln!(out, "  </testcase>")?;
+    }
+
+    writeln!(out, "</testsuite>")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison<'a>(
+        repo: &'a str,
+        expected: &str,
+        deployed: Option<&'a str>,
+        status: Status,
+    ) -> Comparison<'a> {
+        Comparison {
+            expected: Some(expected.to_string()),
+            deployed,
+            ..Comparison::new(repo, status)
+        }
+    }
+
+    fn comparisons() -> Vec<Comparison<'static>> {
+        vec![
+            comparison("scraper", "1.2.0", Some("1.2.0"), Status::UpToDate),
+            comparison("movies-front", "2.0.0", Some("1.9.0"), Status::Drift),
+            comparison("standen-node", "0.1.0", None, Status::NoLambda),
+        ]
+    }
+
+    #[test]
+    fn drift_and_missing_functions_fail() {
+        let mut out = Vec::new();
+        write_junit(&mut out, &comparisons(), "package.json", true).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="beacon2" tests="3" failures="2" errors="0" skipped="0">
+  <testcase classname="beacon2" name="scraper"/>
+  <testcase classname="beacon2" name="movies-front">
+    <failure message="package.json 2.0.0, deployed 1.9.0" type="drift"/>
+  </testcase>
+  <testcase classname="beacon2" name="standen-node">
+    <failure message="No Lambda found" type="missing_function"/>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+
+    #[test]
+    fn missing_functions_are_skipped_when_allowed() {
+        let mut out = Vec::new();
+        write_junit(&mut out, &comparisons(), "package.json", false).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.contains(r#"failures="1" errors="0" skipped="1""#),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains(r#"<skipped message="No Lambda found"/>"#),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn repos_below_the_minimum_fail_alongside_their_status() {
+        let comparisons = vec![
+            Comparison {
+                below_minimum: true,
+                ..comparison("scraper", "1.2.0", Some("1.2.0"), Status::UpToDate)
+            },
+            Comparison {
+                below_minimum: true,
+                ..comparison("movies-front", "1.0.0", Some("0.9.0"), Status::Drift)
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_junit(&mut out, &comparisons, "package.json", true).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.contains(r#"failures="2" errors="0" skipped="0""#),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains(
+                r#"<failure message="package.json 1.2.0 is below the minimum version" type="min_version"/>"#
+            ),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains(
+                r#"<failure message="package.json 1.0.0, deployed 0.9.0; package.json 1.0.0 is below the minimum version" type="drift"/>"#
+            ),
+            "{}",
+            out
+        );
+    }
+}
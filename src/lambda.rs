@@ -0,0 +1,138 @@
+//! The Lambda requests functions are listed and enriched with, behind [`LambdaApi`] so tests
+//! can answer them without AWS credentials.
+
+use crate::aws_error;
+use aws_sdk_lambda::{types::FunctionConfiguration, Client};
+use std::{collections::HashMap, future::Future};
+
+pub trait LambdaApi: Sync {
+    /// Where requests go, for messages.
+    fn region(&self) -> Option<String>;
+
+    /// One page of functions, starting at `marker` when given.
+    fn list_functions(
+        &self,
+        marker: Option<&str>,
+    ) -> impl Future<Output = Result<FunctionPage, anyhow::Error>> + Send;
+
+    /// The full configuration of one function, which the listing can leave
+    /// parts of out, such as its VPC.
+    fn get_function(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<FunctionConfiguration>, anyhow::Error>> + Send;
+
+    fn list_tags(
+        &self,
+        arn: &str,
+    ) -> impl Future<Output = Result<HashMap<String, String>, anyhow::Error>> + Send;
+
+    /// Where the function's deployment package can be downloaded from.
+    fn get_function_code_location(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<CodeLocation, anyhow::Error>> + Send;
+
+    /// One page of the function's provisioned concurrency configs, starting at
+    /// `marker` when given.
+    fn list_provisioned_concurrency(
+        &self,
+        name: &str,
+        marker: Option<&str>,
+    ) -> impl Future<Output = Result<ConcurrencyPage, anyhow::Error>> + Send;
+}
+
+pub struct FunctionPage {
+    pub functions: Vec<FunctionConfiguration>,
+    /// `None` on the last page.
+    pub next_marker: Option<String>,
+}
+
+pub struct CodeLocation {
+    /// The package's size in bytes.
+    pub code_size: i64,
+    /// A presigned URL for a .zip package; `None` for container images.
+    pub url: Option<String>,
+}
+
+pub struct ConcurrencyPage {
+    /// The executions requested by each alias or version.
+    pub requested: Vec<i32>,
+    /// `None` on the last page.
+    pub next_marker: Option<String>,
+}
+
+impl LambdaApi for Client {
+    fn region(&self) -> Option<String> {
+        self.conf().region().map(ToString::to_string)
+    }
+
+    async fn list_functions(&self, marker: Option<&str>) -> Result<FunctionPage, anyhow::Error> {
+        let mut request = Client::list_functions(self);
+        if let Some(marker) = marker {
+            request = request.marker(marker);
+        }
+        let resp = request.send().await.map_err(aws_error)?;
+        Ok(FunctionPage {
+            functions: resp.functions.unwrap_or_default(),
+            next_marker: resp.next_marker,
+        })
+    }
+
+    async fn get_function(
+        &self,
+        name: &str,
+    ) -> Result<Option<FunctionConfiguration>, anyhow::Error> {
+        let resp = Client::get_function(self)
+            .function_name(name)
+            .send()
+            .await
+            .map_err(aws_error)?;
+        Ok(resp.configuration)
+    }
+
+    async fn list_tags(&self, arn: &str) -> Result<HashMap<String, String>, anyhow::Error> {
+        let resp = Client::list_tags(self)
+            .resource(arn)
+            .send()
+            .await
+            .map_err(aws_error)?;
+        Ok(resp.tags.unwrap_or_default())
+    }
+
+    async fn get_function_code_location(&self, name: &str) -> Result<CodeLocation, anyhow::Error> {
+        let resp = Client::get_function(self)
+            .function_name(name)
+            .send()
+            .await
+            .map_err(aws_error)?;
+        Ok(CodeLocation {
+            code_size: resp.configuration().map_or(0, |config| config.code_size()),
+            url: resp
+                .code
+                .filter(|code| code.repository_type() == Some("S3"))
+                .and_then(|code| code.location),
+        })
+    }
+
+    async fn list_provisioned_concurrency(
+        &self,
+        name: &str,
+        marker: Option<&str>,
+    ) -> Result<ConcurrencyPage, anyhow::Error> {
+        let mut request = Client::list_provisioned_concurrency_configs(self).function_name(name);
+        if let Some(marker) = marker {
+            request = request.marker(marker);
+        }
+        let resp = request.send().await.map_err(aws_error)?;
+        Ok(ConcurrencyPage {
+            requested: resp
+                .provisioned_concurrency_configs
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|config| config.requested_provisioned_concurrent_executions())
+                .collect(),
+            next_marker: resp.next_marker,
+        })
+    }
+}
@@ -0,0 +1,3397 @@
+//! Compares the versions in GitHub repos against what's deployed to AWS Lambda.
+//! The `beacon2` binary is [`run`]; the pieces it's built from can be driven
+//! directly, with [`GitHubApi`](github::GitHubApi), [`LambdaApi`](lambda::LambdaApi),
+//! and [`SsmApi`](ssm::SsmApi) standing in for the real services in tests.
+
+/// `eprintln!` for progress messages, which `--quiet` hides.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a warning, which `--summary` lists again at the end.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::summary::warn(format!($($arg)*))
+    };
+}
+
+mod arn;
+mod auth;
+pub mod cache;
+pub mod checkpoint;
+pub mod cli;
+pub mod compare;
+mod config;
+mod dependency;
+mod diff;
+pub mod error;
+mod exit;
+mod fixtures;
+pub mod github;
+mod health;
+mod hooks;
+mod junit;
+pub mod lambda;
+pub mod matching;
+mod matrix;
+mod notify;
+mod policy;
+pub mod redact;
+mod report;
+pub mod repos;
+pub mod shutdown;
+pub mod ssm;
+mod summary;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
+
+use anyhow::{anyhow, Context, Result};
+use arn::Arn;
+use auth::GitHubAuth;
+use aws_config::sts::AssumeRoleProvider;
+use aws_sdk_lambda::{
+    config::Region,
+    error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
+    types::{DeadLetterConfig, FunctionConfiguration, VpcConfigResponse},
+    Client,
+};
+use cache::{ContentCache, ListingCache};
+use checkpoint::{Checkpoint, Listing};
+use cli::{Architecture, Args, AssumeRole, Command, EnvFilter, Format};
+use compare::{DeployedVersion, Status, VersionSource};
+use error::FetchError;
+use exit::AuthError;
+use futures::{future, stream, StreamExt, TryStreamExt};
+use github::{ApiError, Fetched, GitHubApi};
+use http::StatusCode;
+use lambda::{CodeLocation, ConcurrencyPage, FunctionPage, LambdaApi};
+use matching::{MatchOutcome, MatchStrategy, Matcher};
+use matrix::Environment;
+use octocrab::{
+    models::repos::{Content, Release},
+    Octocrab,
+};
+use regex::Regex;
+use repos::Repo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shutdown::Shutdown;
+use ssm::SsmApi;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Set from `--quiet` before anything is printed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Parses the command line and runs what it asks for, exiting with one of the
+/// [`exit`] codes.
+pub async fn run() -> Result<(), anyhow::Error> {
+    let args = match cli::parse() {
+        Ok(args) => args,
+        Err(e) => fail(e, exit::FAILED),
+    };
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    summary::configure(summary::Output {
+        text: args.summary,
+        json: args.summary_json.clone(),
+    });
+    match &args.command {
+        Some(Command::Validate) => validate(&args),
+        Some(Command::ListRepos) => list_repos(&args).await,
+        _ => {}
+    }
+
+    let shutdown = Shutdown::install();
+    let checkpoint = match &args.checkpoint {
+        Some(path) => match Checkpoint::open(path, args.resume) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => fail(e, exit::FAILED),
+        },
+        None => None,
+    };
+
+    if let Some(name) = &args.check_dependency {
+        let (package_jsons, failed) = match load_packagejsons(&args, &shutdown).await {
+            Ok(loaded) => loaded,
+            Err(e) => fail(
+                e.context("Failed to get package.json files"),
+                exit::REPO_FETCH_FAILED,
+            ),
+        };
+
+        let versions: BTreeMap<String, Option<String>> = package_jsons
+            .iter()
+            .map(|(repo, package_json)| {
+                let version = dependency::dependency_version(package_json, name);
+                (repo.clone(), version)
+            })
+            .collect();
+
+        dependency::write_dependency_report(
+            &mut io::stdout().lock(),
+            name,
+            &versions,
+            args.expected.as_deref(),
+        )?;
+
+        if failed > 0 {
+            std::process::exit(exit::REPO_FETCH_FAILED);
+        }
+        if !dependency::diverging(&versions, args.expected.as_deref()).is_empty() {
+            std::process::exit(exit::DRIFT);
+        }
+
+        return Ok(());
+    }
+
+    let matcher = Matcher {
+        strategy: args.match_strategy,
+        fuzzy_threshold: args.fuzzy_threshold,
+        name_template: args.function_name_template.clone(),
+        match_key: args.match_key,
+        strip_scope: args.strip_scope,
+        rules: args.match_rules.clone(),
+        stages: args.stages.clone(),
+        tag_key: args.match_tag.clone(),
+    };
+
+    let version_source = args
+        .version_source
+        .clone()
+        .unwrap_or_else(|| VersionSource::Env(args.version_env_var.clone()));
+    let deployed_version = DeployedVersion {
+        ignore_suffix: args.ignore_version_suffix.clone(),
+        precision: args.compare_precision,
+        ..DeployedVersion::new(version_source, args.version_regex.clone())
+    };
+
+    let filter = LambdaFilter {
+        architecture: args.architecture,
+        vpc_id: args.vpc_id.clone(),
+        env: args.env_filter.clone(),
+    };
+    let aws_cache_ttl = args.aws_cache_ttl.or(args.ttl).filter(|_| !args.no_cache);
+    let cache = ListingCache::new(aws_cache_ttl.map(Duration::from_secs), args.refresh);
+
+    match &args.command {
+        Some(Command::ListLambdas) => list_lambdas(&args, &filter, &cache, &shutdown).await,
+        Some(Command::Report {
+            min_node_major,
+            stale_days,
+        }) => {
+            let rules = health::Rules {
+                min_node_major: *min_node_major,
+                stale_days: *stale_days,
+            };
+            fleet_report(&args, &rules, &filter, &cache, &shutdown).await
+        }
+        _ => {}
+    }
+
+    if let Some(path) = &args.dependency_policy {
+        audit_dependencies(&args, path, &matcher, &filter, &cache, &shutdown).await;
+    }
+
+    if let Some(Command::CompareAllVersions { environments }) = &args.command {
+        let details = load_details(&args, checkpoint.as_ref(), &shutdown);
+        let listing = list_environments(
+            &args,
+            environments,
+            &filter,
+            checkpoint.as_ref(),
+            &cache,
+            &shutdown,
+        );
+        let (details, listed) = if args.parallel_regions_and_github {
+            let (details, listed) = tokio::join!(details, listing);
+            (details_or_exit(details), listed)
+        } else {
+            let details = details_or_exit(details.await);
+            (details, listing.await)
+        };
+        let listed = match listed {
+            Ok(listed) => listed,
+            Err(e) => fail(e, exit::AWS_LISTING_FAILED),
+        };
+
+        let mut deployed = Vec::new();
+        for (environment, mut lambdas, aws_clients) in listed {
+            let clients = lambdas
+                .iter()
+                .map(|fnc| (fnc.arn.clone(), aws_clients.clone()))
+                .collect();
+            if matcher.needs_tags() {
+                let all: Vec<String> = lambdas.iter().map(|fnc| fnc.arn.clone()).collect();
+                if let Err(e) = enrich_tags(&clients, &mut lambdas, &all, &shutdown).await {
+                    fail(e, exit::AWS_LISTING_FAILED);
+                }
+            }
+            let matched = matched_functions(&details, &lambdas, &matcher);
+            if let Err(e) = enrich_deployed_version(
+                &args,
+                &deployed_version.source,
+                &clients,
+                &mut lambdas,
+                &matched,
+                &shutdown,
+            )
+            .await
+            {
+                fail(e, exit::AWS_LISTING_FAILED);
+            }
+            deployed.push((environment, lambdas));
+        }
+
+        let columns: Vec<_> = deployed
+            .iter()
+            .map(|(environment, lambdas)| {
+                let mut comparisons =
+                    compare::compare(&details, lambdas, &deployed_version, &matcher);
+                if let Some(minimum) = &args.min_version {
+                    compare::apply_minimum(&mut comparisons, minimum);
+                }
+                (*environment, comparisons)
+            })
+            .collect();
+
+        matrix::write_matrix(&mut io::stdout().lock(), &details, &columns)?;
+
+        if shutdown.requested() {
+            eprintln!("Run was interrupted, the results above are partial");
+            std::process::exit(shutdown::EXIT_INTERRUPTED);
+        }
+        if let Some(checkpoint) = &checkpoint {
+            checkpoint.finish();
+        }
+
+        let all_comparisons: Vec<_> = columns
+            .into_iter()
+            .flat_map(|(_, comparisons)| comparisons)
+            .collect();
+        let code = exit::for_comparisons(
+            &all_comparisons,
+            args.warn_missing_version,
+            !args.allow_missing_function,
+        );
+        summary::finish(code, &all_comparisons, None);
+        std::process::exit(code);
+    }
+
+    if let Some(Command::Watch { interval }) = &args.command {
+        watch(
+            &args,
+            *interval,
+            &matcher,
+            &deployed_version,
+            &filter,
+            &shutdown,
+        )
+        .await;
+    }
+
+    let scanned = scan(
+        &args,
+        &matcher,
+        &deployed_version,
+        &filter,
+        checkpoint.as_ref(),
+        &cache,
+        &shutdown,
+    )
+    .await;
+    let (details, deployed_lambdas) = scanned.unwrap_or_else(|(e, code)| fail(e, code));
+    let expected_label = expected_label(&args);
+
+    if args.explain {
+        report::write_explanations(
+            &mut io::stderr().lock(),
+            &details,
+            &deployed_lambdas,
+            &matcher,
+        )?;
+    }
+
+    let mut comparisons =
+        compare::compare(&details, &deployed_lambdas, &deployed_version, &matcher);
+    if let Some(minimum) = &args.min_version {
+        compare::apply_minimum(&mut comparisons, minimum);
+    }
+
+    let report_options = report::ReportOptions {
+        expected_label,
+        deployed_label: deployed_version.source.label(),
+        check_dead_letter: args.check_dlq,
+    };
+
+    // With nothing listed, every repo would just be reported as not found.
+    if deployed_lambdas.is_empty() && !args.format.machine_readable() {
+        eprintln!("No functions to compare against, skipping the report");
+    } else {
+        #[cfg(feature = "tui")]
+        if args.tui {
+            tui::run(&comparisons, &report_options.expected_label)?;
+        } else {
+            write_results(&args, &comparisons, &report_options)?;
+        }
+        #[cfg(not(feature = "tui"))]
+        write_results(&args, &comparisons, &report_options)?;
+    }
+
+    // Machine-readable formats keep stdout to the results alone.
+    let mut sections: Box<dyn Write> = if args.format.machine_readable() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
+
+    if args.timings {
+        report::write_timings(&mut sections, &details)?;
+    }
+
+    if let Some(path) = &args.diff_against {
+        match diff::load_previous(path) {
+            Ok(previous) => diff::write_diff(&mut sections, &previous, &comparisons)?,
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    if let Some(command) = &args.on_result {
+        hooks::on_result(command, &comparisons);
+    }
+    if let Some(command) = &args.on_complete {
+        hooks::on_complete(command, &comparisons);
+    }
+    if let Some(summary) = notify::summary(&comparisons, args.notify_threshold) {
+        send_notifications(&args, &summary).await;
+    }
+
+    if args.orphans {
+        report::write_orphans(&mut sections, &comparisons, &deployed_lambdas)?;
+    }
+
+    if args.env_audit {
+        report::write_env_audit(&mut sections, &comparisons)?;
+    }
+
+    if args.compare_env_between_functions {
+        let groups: Vec<_> = details
+            .iter()
+            .map(|(repo, details)| {
+                let package_name = details.package_name.as_deref();
+                let functions = matcher.all_matches(repo, package_name, &deployed_lambdas);
+                (repo.as_str(), functions)
+            })
+            .collect();
+        report::write_env_comparison(&mut sections, &groups)?;
+    }
+
+    if let Some(minimum) = &args.min_version {
+        for comparison in comparisons
+            .iter()
+            .filter(|comparison| comparison.below_minimum)
+        {
+            writeln!(
+                sections,
+                "{} {} is below --min-version {}",
+                comparison.repo,
+                comparison.expected.as_deref().unwrap_or_default(),
+                minimum
+            )?;
+        }
+    }
+
+    if let Some(severity) = args.warn_missing_version {
+        for comparison in comparisons
+            .iter()
+            .filter(|comparison| comparison.status == Status::MissingVersion)
+        {
+            writeln!(
+                sections,
+                "{}: {} has no version in {}",
+                severity.label(),
+                comparison.repo,
+                report_options.expected_label
+            )?;
+        }
+    }
+
+    if shutdown.requested() {
+        eprintln!("Run was interrupted, the results above are partial");
+        summary::finish(shutdown::EXIT_INTERRUPTED, &comparisons, None);
+        std::process::exit(shutdown::EXIT_INTERRUPTED);
+    }
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.finish();
+    }
+
+    let code = exit::for_comparisons(
+        &comparisons,
+        args.warn_missing_version,
+        !args.allow_missing_function,
+    );
+    summary::finish(code, &comparisons, None);
+    std::process::exit(code);
+}
+
+/// Reads the repos and lists the deployed functions, with everything the
+/// comparison and report need looked up. Errors come with the code to exit with.
+async fn scan(
+    args: &Args,
+    matcher: &Matcher,
+    deployed_version: &DeployedVersion,
+    filter: &LambdaFilter,
+    checkpoint: Option<&Checkpoint>,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> Result<(BTreeMap<String, RepoDetails>, Vec<Lambda>), (anyhow::Error, i32)> {
+    if let Some(branch) = &args.compare_branch_vs_deployed {
+        progress!(
+            "Comparing package.json on branch {} against the deployed Lambda functions",
+            branch
+        );
+    }
+    let details = load_details(args, checkpoint, shutdown);
+    let listing = list_targets(args, filter, checkpoint, cache, shutdown);
+    let repo_failed = |e| (e, exit::REPO_FETCH_FAILED);
+    let (details, listed) = if args.parallel_regions_and_github {
+        let (details, listed) = tokio::join!(details, listing);
+        (details.map_err(repo_failed)?, listed)
+    } else {
+        let details = details.await.map_err(repo_failed)?;
+        (details, listing.await)
+    };
+    let aws_failed = |e| (e, exit::AWS_LISTING_FAILED);
+    // Follow-up calls for a function have to use the credentials it was listed with.
+    let (mut deployed_lambdas, clients) = listed.map_err(aws_failed)?;
+
+    if matcher.needs_tags() {
+        let all: Vec<String> = deployed_lambdas.iter().map(|fnc| fnc.arn.clone()).collect();
+        enrich_tags(&clients, &mut deployed_lambdas, &all, shutdown)
+            .await
+            .map_err(aws_failed)?;
+    }
+    let matched = matched_functions(&details, &deployed_lambdas, matcher);
+    if args.check_dlq {
+        enrich_dead_letter(&clients, &mut deployed_lambdas, &matched, shutdown)
+            .await
+            .map_err(aws_failed)?;
+    }
+    if args.show_concurrency {
+        enrich_concurrency(&clients, &mut deployed_lambdas, &matched, shutdown)
+            .await
+            .map_err(aws_failed)?;
+    }
+    enrich_deployed_version(
+        args,
+        &deployed_version.source,
+        &clients,
+        &mut deployed_lambdas,
+        &matched,
+        shutdown,
+    )
+    .await
+    .map_err(aws_failed)?;
+
+    Ok((details, deployed_lambdas))
+}
+
+/// Where the expected version came from, as shown in the report.
+fn expected_label(args: &Args) -> String {
+    match &args.compare_branch_vs_deployed {
+        Some(branch) => format!("{}@{}", Manifest::PackageJson.path(), branch),
+        None if args.latest_release => {
+            format!("{}@latest release", Manifest::from_args(args).path())
+        }
+        None => Manifest::from_args(args).label(),
+    }
+}
+
+/// The `watch` subcommand: scans every `interval` until Ctrl-C, writing the
+/// results and sending notifications only when they differ from the last scan's.
+/// A failed scan is reported and retried at the next interval.
+async fn watch(
+    args: &Args,
+    interval: Duration,
+    matcher: &Matcher,
+    deployed_version: &DeployedVersion,
+    filter: &LambdaFilter,
+    shutdown: &Shutdown,
+) -> ! {
+    let report_options = report::ReportOptions {
+        expected_label: expected_label(args),
+        deployed_label: deployed_version.source.label(),
+        check_dead_letter: args.check_dlq,
+    };
+    let aws_cache_ttl = args.aws_cache_ttl.or(args.ttl).filter(|_| !args.no_cache);
+    let mut previous = None;
+
+    loop {
+        // A fresh cache each time, so functions are listed again rather than
+        // reused from the first scan.
+        let cache = ListingCache::new(aws_cache_ttl.map(Duration::from_secs), args.refresh);
+        let scanned = scan(
+            args,
+            matcher,
+            deployed_version,
+            filter,
+            None,
+            &cache,
+            shutdown,
+        )
+        .await;
+
+        match scanned {
+            // An interrupted scan is partial, so it's not compared or reported.
+            Ok(_) if shutdown.requested() => {}
+            Ok((details, deployed_lambdas)) => {
+                let mut comparisons =
+                    compare::compare(&details, &deployed_lambdas, deployed_version, matcher);
+                if let Some(minimum) = &args.min_version {
+                    compare::apply_minimum(&mut comparisons, minimum);
+                }
+
+                let state = watch::fingerprint(&comparisons);
+                if previous.as_ref() == Some(&state) {
+                    progress!("No changes since the last scan");
+                } else {
+                    if let Err(e) = write_results(args, &comparisons, &report_options) {
+                        eprintln!("Failed to write the results: {}", e);
+                    }
+                    if let Some(summary) = notify::summary(&comparisons, args.notify_threshold) {
+                        send_notifications(args, &summary).await;
+                    }
+                    previous = Some(state);
+                }
+            }
+            Err((e, _)) => eprintln!("Scan failed, retrying at the next interval: {:#}", e),
+        }
+
+        if !watch::sleep(interval, shutdown).await {
+            std::process::exit(exit::SUCCESS);
+        }
+    }
+}
+
+/// The `list-repos` subcommand.
+async fn list_repos(args: &Args) -> ! {
+    let repos = match repo_list(args).await {
+        Ok(repos) => repos,
+        Err(e) => fail(e, exit::REPO_FETCH_FAILED),
+    };
+    for repo in repos {
+        match &repo.git_ref {
+            Some(git_ref) => println!("{}@{}", repo, git_ref),
+            None => println!("{}", repo),
+        }
+    }
+    std::process::exit(exit::SUCCESS);
+}
+
+/// The `list-lambdas` subcommand: one function per line, or the functions
+/// serialized for `--format json`, `yaml`, or `ndjson`.
+async fn list_lambdas(
+    args: &Args,
+    filter: &LambdaFilter,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> ! {
+    let lambdas = match list_targets(args, filter, None, cache, shutdown).await {
+        Ok((lambdas, _)) => lambdas,
+        Err(e) => fail(e, exit::AWS_LISTING_FAILED),
+    };
+
+    let mut out = io::stdout().lock();
+    let written = match args.format {
+        Format::Json => serde_json::to_writer_pretty(&mut out, &lambdas)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(out)),
+        Format::Yaml => serde_yaml::to_writer(&mut out, &lambdas).map_err(io::Error::other),
+        Format::Ndjson => lambdas.iter().try_for_each(|fnc| {
+            serde_json::to_writer(&mut out, fnc)?;
+            writeln!(out)
+        }),
+        _ => lambdas
+            .iter()
+            .try_for_each(|fnc| writeln!(out, "{} {}", fnc.name, fnc.arn)),
+    };
+    if let Err(e) = written {
+        fail(e.into(), exit::FAILED);
+    }
+
+    if shutdown.requested() {
+        eprintln!("Run was interrupted, the results above are partial");
+        std::process::exit(shutdown::EXIT_INTERRUPTED);
+    }
+    std::process::exit(exit::SUCCESS);
+}
+
+/// The `report` subcommand, exiting with `exit::DRIFT` if any function is flagged.
+async fn fleet_report(
+    args: &Args,
+    rules: &health::Rules,
+    filter: &LambdaFilter,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> ! {
+    let lambdas = match list_targets(args, filter, None, cache, shutdown).await {
+        Ok((lambdas, _)) => lambdas,
+        Err(e) => fail(e, exit::AWS_LISTING_FAILED),
+    };
+
+    let today = health::days_since_epoch(std::time::SystemTime::now());
+    let flagged = match health::write_report(&mut io::stdout().lock(), &lambdas, rules, today) {
+        Ok(flagged) => flagged,
+        Err(e) => fail(e.into(), exit::FAILED),
+    };
+
+    if shutdown.requested() {
+        eprintln!("Run was interrupted, the results above are partial");
+        std::process::exit(shutdown::EXIT_INTERRUPTED);
+    }
+    std::process::exit(if flagged == 0 {
+        exit::SUCCESS
+    } else {
+        exit::DRIFT
+    });
+}
+
+/// `--dependency-policy`: reports the repos violating the policy at `path`, with
+/// the functions they're deployed as, and exits with `exit::DRIFT` if any do, or
+/// `exit::REPO_FETCH_FAILED` if some package.json couldn't be read.
+async fn audit_dependencies(
+    args: &Args,
+    path: &Path,
+    matcher: &Matcher,
+    filter: &LambdaFilter,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> ! {
+    let policy = match policy::Policy::load(path) {
+        Ok(policy) => policy,
+        Err(e) => fail(e, exit::FAILED),
+    };
+    let (package_jsons, failed) = match load_packagejsons(args, shutdown).await {
+        Ok(loaded) => loaded,
+        Err(e) => fail(
+            e.context("Failed to get package.json files"),
+            exit::REPO_FETCH_FAILED,
+        ),
+    };
+    let deployed_lambdas = match list_targets(args, filter, None, cache, shutdown).await {
+        Ok((lambdas, _)) => lambdas,
+        Err(e) => fail(e, exit::AWS_LISTING_FAILED),
+    };
+
+    let results: Vec<_> = package_jsons
+        .iter()
+        .filter_map(|(repo, package_json)| {
+            let violations = policy.violations(package_json);
+            if violations.is_empty() {
+                return None;
+            }
+            let package_name = package_json.get("name").and_then(Value::as_str);
+            let function = match matcher.find(repo, package_name, &deployed_lambdas) {
+                MatchOutcome::Matched { lambda, .. } => Some(lambda.name.as_str()),
+                _ => None,
+            };
+            Some((repo.as_str(), function, violations))
+        })
+        .collect();
+
+    if let Err(e) = policy::write_policy_report(&mut io::stdout().lock(), &results) {
+        fail(e.into(), exit::FAILED);
+    }
+
+    if shutdown.requested() {
+        eprintln!("Run was interrupted, the results above are partial");
+        std::process::exit(shutdown::EXIT_INTERRUPTED);
+    }
+    std::process::exit(if failed > 0 {
+        exit::REPO_FETCH_FAILED
+    } else if results.is_empty() {
+        exit::SUCCESS
+    } else {
+        exit::DRIFT
+    });
+}
+
+/// Prints every problem with `--config` and exits, non-zero if there were any.
+fn validate(args: &Args) -> ! {
+    let Some(path) = &args.config else {
+        fail(anyhow!("Nothing to validate, pass --config"), exit::FAILED);
+    };
+    let problems = match config::Config::check(path, args) {
+        Ok(problems) => problems,
+        Err(e) => fail(e, exit::FAILED),
+    };
+
+    if problems.is_empty() {
+        println!("{} is valid", path.display());
+        std::process::exit(exit::SUCCESS);
+    }
+    println!("{} has {} problem(s):", path.display(), problems.len());
+    for problem in problems {
+        println!("  {}", problem);
+    }
+    std::process::exit(exit::FAILED);
+}
+
+/// Prints the comparisons in the `--format` chosen.
+fn write_results(
+    args: &Args,
+    comparisons: &[compare::Comparison],
+    report_options: &report::ReportOptions,
+) -> io::Result<()> {
+    if let Some(template) = &args.template {
+        return report::write_templated(&mut io::stdout().lock(), comparisons, template);
+    }
+
+    match args.format {
+        Format::Text if args.group_by_status => {
+            report::write_grouped_report(&mut io::stdout().lock(), comparisons, report_options)
+        }
+        Format::Text => report::write_report(&mut io::stdout().lock(), comparisons, report_options),
+        Format::Junit => junit::write_junit(
+            &mut io::stdout().lock(),
+            comparisons,
+            &report_options.expected_label,
+            !args.allow_missing_function,
+        ),
+        Format::Table => report::write_table(
+            &mut io::stdout().lock(),
+            comparisons,
+            &report_options.expected_label,
+        ),
+        Format::Json => report::write_json(&mut io::stdout().lock(), comparisons, args.stable),
+        Format::Yaml => report::write_yaml(&mut io::stdout().lock(), comparisons, args.stable),
+        Format::Ndjson => {
+            for comparison in comparisons {
+                report::write_ndjson_line(&mut io::stdout().lock(), comparison, args.stable)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Shows up in CloudTrail as the session that assumed `--role-arn`.
+const ASSUME_ROLE_SESSION_NAME: &str = "beacon2";
+
+/// Prints why the run failed and exits with the code for that kind of failure.
+fn fail(e: anyhow::Error, fallback: i32) -> ! {
+    eprintln!("{:#}", e);
+    let code = exit::for_error(&e, fallback);
+    summary::finish(code, &[], Some(&e));
+    std::process::exit(code);
+}
+
+fn github_cache(args: &Args, auth: &GitHubAuth) -> ContentCache {
+    let ttl = Duration::from_secs(args.ttl.unwrap_or(0));
+    ContentCache::new(ttl, !args.no_cache, &auth.identity())
+}
+
+/// Failures are only warned about, so a broken integration never hides the report.
+async fn send_notifications(args: &Args, summary: &notify::Summary) {
+    if let Some(webhook) = &args.slack_webhook {
+        if let Err(e) = notify::slack(webhook, summary).await {
+            warning!("{}", e);
+        }
+    }
+
+    if let Some(topic_arn) = &args.sns_topic_arn {
+        // The topic can live in another region than the functions.
+        let region = Arn::parse(topic_arn)
+            .map(|arn| arn.region.to_string())
+            .filter(|region| !region.is_empty())
+            .or_else(|| args.region.clone());
+        let config = load_aws_config(
+            region,
+            args.profile.clone(),
+            args.role_arn.as_deref(),
+            args.aws_endpoint_url.as_deref(),
+        )
+        .await;
+        let client = aws_sdk_sns::Client::new(&config);
+        if let Err(e) = notify::sns(&client, topic_arn, summary).await {
+            warning!("{}", e);
+        }
+    }
+}
+
+/// The GitHub App when `--github-app-id` is given, otherwise the token.
+async fn github_auth(args: &Args) -> Result<GitHubAuth, anyhow::Error> {
+    if let (Some(app_id), Some(key)) = (args.github_app_id, &args.github_app_key) {
+        let owner = args.org.as_deref().unwrap_or(&args.owner);
+        return GitHubAuth::app(app_id, key, args.github_installation_id, owner).await;
+    }
+
+    let token = args.github_token.clone().unwrap_or_else(|| {
+        eprintln!(
+            "No GitHub token, pass --github-token or set MY_TOKEN, or pass --github-app-id and --github-app-key"
+        );
+        std::process::exit(exit::FAILED);
+    });
+    GitHubAuth::token(token)
+}
+
+/// Reads full package.json files from fixtures or GitHub, depending on `args`.
+async fn load_packagejsons(
+    args: &Args,
+    shutdown: &Shutdown,
+) -> Result<(BTreeMap<String, HashMap<String, Value>>, usize), anyhow::Error> {
+    if let Some(dir) = &args.fixtures {
+        return Ok((fixtures::load_packagejsons(dir)?, 0));
+    }
+
+    let repos = repo_list(args).await?;
+    let auth = github_auth(args).await?;
+    let cache = github_cache(args, &auth);
+    let options = FetchOptions {
+        git_ref: args.git_ref.as_deref(),
+        latest_release: args.latest_release,
+        max_file_size: args.max_file_size,
+        cache: &cache,
+    };
+    let repos = if args.discover_packages {
+        discover_packages(&auth, repos, options, args.concurrency).await
+    } else {
+        repos
+    };
+    fetch_packagejsons(&auth, &repos, options, args.concurrency, shutdown).await
+}
+
+/// Reads the expected versions from fixtures or GitHub, depending on `args`.
+async fn load_details(
+    args: &Args,
+    checkpoint: Option<&Checkpoint>,
+    shutdown: &Shutdown,
+) -> Result<BTreeMap<String, RepoDetails>, anyhow::Error> {
+    if let Some(dir) = &args.fixtures {
+        return fixtures::load_packagejson_details(dir)
+            .context("Failed to load package.json fixtures");
+    }
+
+    let repos = repo_list(args).await?;
+    let auth = github_auth(args).await?;
+
+    let manifest = Manifest::from_args(args);
+
+    let cache = github_cache(args, &auth);
+    let options = FetchOptions {
+        git_ref: args
+            .compare_branch_vs_deployed
+            .as_deref()
+            .or(args.git_ref.as_deref()),
+        latest_release: args.latest_release,
+        max_file_size: args.max_file_size,
+        cache: &cache,
+    };
+    let repos = if args.discover_packages {
+        discover_packages(&auth, repos, options, args.concurrency).await
+    } else {
+        repos
+    };
+
+    fetch_packagejson_details(
+        &auth,
+        &repos,
+        &manifest,
+        options,
+        args.concurrency,
+        checkpoint,
+        shutdown,
+    )
+    .await
+    .context("Failed to get package.json details")
+}
+
+fn details_or_exit(
+    details: Result<BTreeMap<String, RepoDetails>, anyhow::Error>,
+) -> BTreeMap<String, RepoDetails> {
+    details.unwrap_or_else(|e| fail(e, exit::REPO_FETCH_FAILED))
+}
+
+/// Lists the functions in each `compare-all-versions` environment, with the
+/// clients they were listed with.
+async fn list_environments<'e>(
+    args: &Args,
+    environments: &'e [Environment],
+    filter: &LambdaFilter,
+    checkpoint: Option<&Checkpoint>,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> Result<Vec<(&'e Environment, Vec<Lambda>, AwsClients)>, anyhow::Error> {
+    let redactor = redactor(args);
+    let redactor = redactor.as_ref();
+    let listings = environments.iter().map(|environment| async move {
+        if shutdown.requested() {
+            return Ok(None);
+        }
+
+        let config = load_aws_config(
+            environment.region.clone(),
+            environment.profile.clone(),
+            args.role_arn.as_deref(),
+            args.aws_endpoint_url.as_deref(),
+        )
+        .await;
+        let region = require_region(&config);
+        progress!(
+            "Listing Lambda functions for {} in {}",
+            environment.name,
+            region
+        );
+
+        let aws_clients = AwsClients::new(&config);
+        let (target, cache) = listing_target(
+            &config,
+            environment.profile.as_deref(),
+            args.role_arn.as_deref(),
+            args.aws_endpoint_url.as_deref(),
+            cache,
+        )
+        .await;
+        let lambdas = list_functions(
+            &aws_clients.lambda,
+            filter,
+            &target,
+            redactor,
+            checkpoint,
+            cache,
+            shutdown,
+        )
+        .await?;
+        Ok::<_, anyhow::Error>(Some((environment, lambdas, aws_clients)))
+    });
+
+    stream::iter(listings)
+        .buffered(args.concurrency)
+        .try_filter_map(future::ok)
+        .try_collect()
+        .await
+}
+
+/// Lists the functions in every `--assume-role` target, or the single default one,
+/// keeping which clients each function was listed with by ARN.
+async fn list_targets(
+    args: &Args,
+    filter: &LambdaFilter,
+    checkpoint: Option<&Checkpoint>,
+    cache: &ListingCache,
+    shutdown: &Shutdown,
+) -> Result<(Vec<Lambda>, HashMap<String, AwsClients>), anyhow::Error> {
+    let regions: Vec<Option<String>> = if args.regions.is_empty() {
+        vec![args.region.clone()]
+    } else {
+        args.regions.iter().cloned().map(Some).collect()
+    };
+
+    let targets = listing_targets(&args.assume_role, args.role_arn.as_deref(), &regions);
+
+    // Each listing's pages chain on the previous page's marker, so it's the
+    // targets that are listed side by side, in order so the results are too.
+    let redactor = redactor(args);
+    let redactor = redactor.as_ref();
+    let listings = targets.into_iter().map(|(role_arn, region)| async move {
+        if shutdown.requested() {
+            return Ok(None);
+        }
+
+        let config = load_aws_config(
+            region,
+            args.profile.clone(),
+            role_arn,
+            args.aws_endpoint_url.as_deref(),
+        )
+        .await;
+        let region = require_region(&config);
+        match role_arn {
+            Some(role_arn) => progress!("Listing Lambda functions in {} as {}", region, role_arn),
+            None => progress!("Listing Lambda functions in {}", region),
+        }
+
+        let aws_clients = AwsClients::new(&config);
+        let (target, cache) = listing_target(
+            &config,
+            args.profile.as_deref(),
+            role_arn,
+            args.aws_endpoint_url.as_deref(),
+            cache,
+        )
+        .await;
+        let lambdas = list_functions(
+            &aws_clients.lambda,
+            filter,
+            &target,
+            redactor,
+            checkpoint,
+            cache,
+            shutdown,
+        )
+        .await?;
+        Ok::<_, anyhow::Error>(Some((lambdas, aws_clients)))
+    });
+    let listed: Vec<(Vec<Lambda>, AwsClients)> = stream::iter(listings)
+        .buffered(args.concurrency)
+        .try_filter_map(future::ok)
+        .try_collect()
+        .await?;
+
+    let mut deployed_lambdas = Vec::new();
+    let mut clients: HashMap<String, AwsClients> = HashMap::new();
+    for (lambdas, aws_clients) in listed {
+        for fnc in &lambdas {
+            clients.insert(fnc.arn.clone(), aws_clients.clone());
+        }
+        deployed_lambdas.extend(lambdas);
+    }
+
+    Ok((deployed_lambdas, clients))
+}
+
+/// Hides secret-looking env var values from everything printed, unless
+/// `--show-secrets`. The deployed version's key is never hidden.
+fn redactor(args: &Args) -> Option<redact::Redactor> {
+    if args.show_secrets {
+        return None;
+    }
+
+    let version_key = match &args.version_source {
+        Some(VersionSource::Env(key)) => key,
+        _ => &args.version_env_var,
+    };
+    let mut allow = args.allow_keys.clone();
+    allow.push(Regex::new(&format!("^{}$", regex::escape(version_key))).unwrap());
+    Some(redact::Redactor {
+        allow,
+        deny: args.redact_keys.clone(),
+    })
+}
+
+/// What a listing with `config` is saved under for `--checkpoint` and the
+/// function cache, and the cache to use for it. Listings are only kept across
+/// runs under the account they came from, so when that can't be told, the
+/// cache is skipped.
+async fn listing_target<'c>(
+    config: &aws_config::SdkConfig,
+    profile: Option<&str>,
+    role_arn: Option<&str>,
+    endpoint_url: Option<&str>,
+    cache: &'c ListingCache,
+) -> (String, Option<&'c ListingCache>) {
+    let account = if cache.persistent() {
+        caller_account(config).await
+    } else {
+        None
+    };
+    let region = require_region(config);
+    let target = target_label(account.as_deref(), profile, role_arn, endpoint_url, region);
+    let cache = (account.is_some() || !cache.persistent()).then_some(cache);
+    (target, cache)
+}
+
+/// The account `config`'s credentials belong to. Credentials from the
+/// environment or the default profile don't say which one that is otherwise.
+async fn caller_account(config: &aws_config::SdkConfig) -> Option<String> {
+    match aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await
+    {
+        Ok(identity) => identity.account().map(str::to_string),
+        Err(e) => {
+            warning!(
+                "could not tell which AWS account is being listed, so its functions won't be cached: {}",
+                aws_sdk_sts::error::DisplayErrorContext(&e)
+            );
+            None
+        }
+    }
+}
+
+/// The role (if any) and region of each listing. Without `--assume-role` there's
+/// one per region for the default chain, or `role_arn`. Roles without a region of
+/// their own are listed in every region.
+fn listing_targets<'a>(
+    assume_role: &'a [AssumeRole],
+    role_arn: Option<&'a str>,
+    regions: &[Option<String>],
+) -> Vec<(Option<&'a str>, Option<String>)> {
+    if assume_role.is_empty() {
+        return regions
+            .iter()
+            .map(|region| (role_arn, region.clone()))
+            .collect();
+    }
+
+    assume_role
+        .iter()
+        .flat_map(|target| {
+            let role_arn = Some(target.role_arn.as_str());
+            match &target.region {
+                Some(region) => vec![(role_arn, Some(region.clone()))],
+                None => regions
+                    .iter()
+                    .map(|region| (role_arn, region.clone()))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Names what a listing came from, for `--checkpoint` and the function cache.
+fn target_label(
+    account: Option<&str>,
+    profile: Option<&str>,
+    role_arn: Option<&str>,
+    endpoint_url: Option<&str>,
+    region: &Region,
+) -> String {
+    let credentials: Vec<&str> = profile.into_iter().chain(role_arn).collect();
+    let credentials = if credentials.is_empty() {
+        "default".to_string()
+    } else {
+        credentials.join(" as ")
+    };
+    let mut label = format!("{}@{}", credentials, region);
+    if let Some(account) = account {
+        label = format!("{} in {}", label, account);
+    }
+    if let Some(endpoint_url) = endpoint_url {
+        label = format!("{} via {}", label, endpoint_url);
+    }
+    label
+}
+
+/// Lists the functions in `target`, reusing an earlier listing from `cache`
+/// when there is one. Interrupted listings aren't cached, and secrets are
+/// redacted before anything is; with `--show-secrets` the cache isn't used at
+/// all, so they're never written out.
+async fn list_functions(
+    client: &Client,
+    filter: &LambdaFilter,
+    target: &str,
+    redactor: Option<&redact::Redactor>,
+    checkpoint: Option<&Checkpoint>,
+    cache: Option<&ListingCache>,
+    shutdown: &Shutdown,
+) -> Result<Vec<Lambda>, anyhow::Error> {
+    // The filters are applied while listing, so they're part of what was cached.
+    let key = format!("{} {:?}", target, filter);
+    let cache = cache.filter(|_| redactor.is_some());
+    if let Some(functions) = cache.and_then(|cache| cache.get(&key)) {
+        return Ok(functions);
+    }
+
+    let listing = checkpoint.map(|checkpoint| (checkpoint, target));
+    let functions = get_deployed_lambdas_list(client, filter, redactor, listing, shutdown).await?;
+    if let (Some(cache), false) = (cache, shutdown.requested()) {
+        cache.put(&key, &functions);
+    }
+    Ok(functions)
+}
+
+/// The repos to scan: read from stdin with `--repos-stdin` or listed from `--org`,
+/// otherwise those in `--config` or the defaults, narrowed by `--only-repos` and
+/// `--skip-repos`, and capped by `--max-repos` so a mistaken list can't fire off
+/// thousands of requests.
+async fn repo_list(args: &Args) -> Result<Vec<Repo>, anyhow::Error> {
+    let repos = if args.repos_stdin {
+        let input = io::read_to_string(io::stdin())
+            .map_err(|e| anyhow!("Failed to read repo list from stdin: {}", e))?;
+        repos::parse_repo_list(&input, &args.owner)?
+    } else if let Some(owner) = &args.org {
+        let filter = repos::OrgFilter {
+            topics: &args.topic,
+            language: args.language.as_deref(),
+            name_pattern: args.name_pattern.as_ref(),
+        };
+        let octocrab = github_auth(args).await?.client().await?;
+        list_owner_repos(&octocrab, owner, &filter).await?
+    } else if !args.config_repos.is_empty() {
+        args.config_repos.clone()
+    } else {
+        repos::default_repos(&args.owner)
+    };
+
+    let repos = repos::filter_repos(repos, &args.only_repos, &args.skip_repos);
+    if repos.len() > args.max_repos && !args.yes {
+        return Err(anyhow!(
+            "{} repos to scan is over --max-repos {}; pass --yes or raise --max-repos to continue",
+            repos.len(),
+            args.max_repos
+        ));
+    }
+
+    Ok(repos)
+}
+
+/// Loads the shared AWS config. Without an explicit region the SDK's default chain
+/// infers one from `AWS_REGION`/`AWS_DEFAULT_REGION`, the active profile, or IMDS.
+///
+/// Credentials come from the default chain too, which covers GitHub Actions OIDC
+/// (`AWS_ROLE_ARN` plus `AWS_WEB_IDENTITY_TOKEN_FILE`) without stored keys. With
+/// `role_arn`, those credentials are only used to assume that role via STS.
+///
+/// `endpoint_url` sends every service's requests there instead, e.g. to LocalStack.
+async fn load_aws_config(
+    region: Option<String>,
+    profile: Option<String>,
+    role_arn: Option<&str>,
+    endpoint_url: Option<&str>,
+) -> aws_config::SdkConfig {
+    let mut loader = aws_config::from_env();
+    if let Some(profile) = profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region));
+    }
+    if let Some(endpoint_url) = endpoint_url {
+        loader = loader.endpoint_url(endpoint_url);
+    }
+    let config = loader.load().await;
+
+    let (Some(role_arn), Some(region), Some(credentials)) =
+        (role_arn, config.region(), config.credentials_provider())
+    else {
+        return config;
+    };
+
+    let assume_role = AssumeRoleProvider::builder(role_arn)
+        .session_name(ASSUME_ROLE_SESSION_NAME)
+        .region(region.clone())
+        .build(credentials.clone());
+
+    let mut loader = aws_config::from_env()
+        .region(region.clone())
+        .credentials_provider(assume_role);
+    if let Some(endpoint_url) = endpoint_url {
+        loader = loader.endpoint_url(endpoint_url);
+    }
+    loader.load().await
+}
+
+fn require_region(config: &aws_config::SdkConfig) -> &Region {
+    config.region().unwrap_or_else(|| {
+        eprintln!("No AWS region configured, pass --region or set AWS_REGION");
+        std::process::exit(exit::FAILED);
+    })
+}
+
+/// The expected version read from a repo (`None` when the manifest has none, or
+/// why it couldn't be read), and how long the fetch took.
+pub struct RepoDetails {
+    pub version: Result<Option<Value>, FetchError>,
+    /// package.json's `name`, when the version came from package.json.
+    pub package_name: Option<String>,
+    /// The commit the manifest was read at, when it came from the repo tree.
+    pub commit: Option<String>,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Lambda {
+    pub name: String,
+    pub env_vars: HashMap<String, String>,
+    pub arn: String,
+    /// The account the function lives in, from its ARN.
+    pub account_id: Option<String>,
+    /// The region the function lives in, from its ARN, so functions listed across
+    /// `--regions` can be told apart.
+    pub region: Option<String>,
+    /// The ARN's partition: `aws`, or e.g. `aws-us-gov` or `aws-cn`.
+    pub partition: Option<String>,
+    pub architectures: Vec<String>,
+    /// e.g. `nodejs20.x`; `None` for container images, which have no managed runtime.
+    pub runtime: Option<String>,
+    pub memory_size: Option<i32>,
+    /// In seconds.
+    pub timeout: Option<i32>,
+    /// As Lambda reports it, e.g. `2024-05-01T12:34:56.000+0000`.
+    pub last_modified: Option<String>,
+    /// The deployment package's size in bytes.
+    pub code_size: Option<i64>,
+    pub vpc: Option<Vpc>,
+    pub dead_letter_target: Option<String>,
+    pub description: Option<String>,
+    /// Only read for matched functions, and only when a version comes from a tag.
+    pub tags: HashMap<String, String>,
+    /// Only read for matched functions, and only when a version comes from SSM.
+    pub ssm_version: Option<String>,
+    /// Only read for matched functions, and only when a version comes from a
+    /// file in the deployment package.
+    pub package_version: Option<String>,
+    /// Only read for matched functions, and only when a version comes from a
+    /// version endpoint.
+    pub http_version: Option<String>,
+    /// Requested executions summed over every alias and version, `Some(0)` when
+    /// none is configured. Only read for matched functions with `--show-concurrency`.
+    pub provisioned_concurrency: Option<i32>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Vpc {
+    /// `None` when the function isn't attached to a VPC.
+    pub vpc_id: Option<String>,
+    pub subnet_ids: Vec<String>,
+}
+
+impl Vpc {
+    fn from_config(config: &VpcConfigResponse) -> Self {
+        Vpc {
+            vpc_id: config
+                .vpc_id()
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+            subnet_ids: config.subnet_ids().unwrap_or_default().to_vec(),
+        }
+    }
+}
+
+/// The account, region, and partition fields of `arn`.
+fn arn_location(arn: &str) -> (Option<String>, Option<String>, Option<String>) {
+    let present = |field: &str| Some(field).filter(|f| !f.is_empty()).map(str::to_string);
+    match Arn::parse(arn) {
+        Some(arn) => (
+            present(arn.account_id),
+            present(arn.region),
+            Some(arn.partition.to_string()),
+        ),
+        None => (None, None, None),
+    }
+}
+
+fn dead_letter_target(config: Option<&DeadLetterConfig>) -> Option<String> {
+    config
+        .and_then(|config| config.target_arn())
+        .filter(|arn| !arn.is_empty())
+        .map(str::to_string)
+}
+
+/// The ARNs of the functions some repo matches, in any account or region.
+fn matched_functions(
+    details: &BTreeMap<String, RepoDetails>,
+    deployed_lambdas: &[Lambda],
+    matcher: &Matcher,
+) -> Vec<String> {
+    details
+        .iter()
+        .flat_map(|(repo, details)| {
+            matcher.find_each_location(repo, details.package_name.as_deref(), deployed_lambdas)
+        })
+        .filter_map(|outcome| match outcome {
+            MatchOutcome::Matched { lambda, .. } => Some(lambda.arn.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Tags and SSM parameters aren't part of the listing, so they're read for the
+/// `matched` functions only, and only when `source` needs them.
+async fn enrich_deployed_version(
+    args: &Args,
+    source: &VersionSource,
+    clients: &HashMap<String, AwsClients>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    match source {
+        // Tag matching already read every function's tags.
+        VersionSource::Tag(_) if args.match_strategy == MatchStrategy::Tag => Ok(()),
+        VersionSource::Tag(_) => enrich_tags(clients, deployed_lambdas, matched, shutdown).await,
+        VersionSource::Ssm(template) => {
+            enrich_ssm_version(template, clients, deployed_lambdas, matched, shutdown).await
+        }
+        VersionSource::Package(path) => {
+            enrich_package_version(
+                path,
+                args.max_package_size,
+                clients,
+                deployed_lambdas,
+                matched,
+                shutdown,
+            )
+            .await
+        }
+        VersionSource::Http(template) => {
+            let timeout = Duration::from_secs(args.http_timeout);
+            enrich_http_version(template, timeout, deployed_lambdas, matched, shutdown).await
+        }
+        VersionSource::Env(_) | VersionSource::Description => Ok(()),
+    }
+}
+
+async fn enrich_tags(
+    clients: &HashMap<String, impl LambdaApi>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let Some(client) = clients.get(&fnc.arn) else {
+            continue;
+        };
+        fnc.tags = client.list_tags(&fnc.arn).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads the SSM parameter `template` names for each `matched` function, with
+/// `{function}` replaced by the function name. A missing parameter leaves the
+/// deployed version unknown rather than failing the run.
+async fn enrich_ssm_version(
+    template: &str,
+    clients: &HashMap<String, impl SsmApi>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let Some(client) = clients.get(&fnc.arn) else {
+            continue;
+        };
+        let name = template.replace("{function}", &fnc.name);
+        fnc.ssm_version = client.get_parameter(&name).await?;
+        if fnc.ssm_version.is_none() {
+            warning!("no SSM parameter {} for function {}", name, fnc.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Downloads each `matched` function's deployment package from the presigned URL
+/// get_function returns, and reads `path` inside it. Packages over
+/// `max_package_size` bytes, container images, and packages without the file
+/// leave the deployed version unknown rather than failing the run.
+async fn enrich_package_version(
+    path: &str,
+    max_package_size: u64,
+    clients: &HashMap<String, impl LambdaApi>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let Some(client) = clients.get(&fnc.arn) else {
+            continue;
+        };
+        let CodeLocation { code_size, url } = client.get_function_code_location(&fnc.arn).await?;
+        let read = match url.as_deref() {
+            None => Err(anyhow!("it is not deployed as a .zip package")),
+            Some(_) if u64::try_from(code_size).is_ok_and(|size| size > max_package_size) => {
+                Err(anyhow!(
+                    "the package is {} bytes, over the {} byte limit (see --max-package-size)",
+                    code_size,
+                    max_package_size
+                ))
+            }
+            Some(location) => read_package_file(location, path, max_package_size).await,
+        };
+
+        match read {
+            Ok(Some(version)) => fnc.package_version = Some(version),
+            Ok(None) => warning!("no {} in the package of function {}", path, fnc.name),
+            Err(e) => warning!(
+                "failed to read {} from the package of function {}: {}",
+                path,
+                fnc.name,
+                e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// The trimmed contents of `path` in the .zip at `url`, or `None` when it has no such file.
+async fn read_package_file(
+    url: &str,
+    path: &str,
+    max_package_size: u64,
+) -> Result<Option<String>, anyhow::Error> {
+    let too_large = || {
+        anyhow!(
+            "the package is over the {} byte limit (see --max-package-size)",
+            max_package_size
+        )
+    };
+    let mut resp = reqwest::get(url).await?.error_for_status()?;
+    if resp
+        .content_length()
+        .is_some_and(|length| length > max_package_size)
+    {
+        return Err(too_large());
+    }
+    // A chunked response has no length up front, so the limit is also held to
+    // while reading.
+    let mut bytes = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if bytes.len() as u64 + chunk.len() as u64 > max_package_size {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    io::Read::read_to_string(&mut file, &mut content)?;
+    Ok(Some(content.trim().to_string()))
+}
+
+/// GETs the URL `template` gives for each `matched` function, with `{function}`
+/// replaced by the function name. Timeouts, failed requests, and non-200
+/// responses leave the deployed version unknown rather than failing the run.
+async fn enrich_http_version(
+    template: &str,
+    timeout: Duration,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let url = template.replace("{function}", &fnc.name);
+        match get_http_version(&client, &url).await {
+            Ok(version) => fnc.http_version = Some(version),
+            Err(e) => warning!(
+                "failed to read the version of function {} from {}: {}",
+                fnc.name,
+                url,
+                e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_http_version(client: &reqwest::Client, url: &str) -> Result<String, anyhow::Error> {
+    let resp = client.get(url).send().await?;
+    if resp.status() != reqwest::StatusCode::OK {
+        return Err(anyhow!("got {}", resp.status()));
+    }
+    parse_http_version(&resp.text().await?)
+}
+
+/// The `version` field of a JSON object. Anything else, e.g. an HTML error page
+/// served with a 200, leaves the version unknown rather than being taken for one.
+fn parse_http_version(body: &str) -> Result<String, anyhow::Error> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|e| anyhow!("response isn't JSON: {}", e))?;
+    match json.get("version") {
+        Some(Value::Null) | None => Err(anyhow!("response has no `version` field")),
+        Some(version) => Ok(compare::version_string(version)),
+    }
+}
+
+/// The listing doesn't always carry dead-letter config, so re-read it with
+/// get_function, but only for the `matched` functions that appear to lack one.
+/// `clients` maps each function ARN to the clients it was listed with.
+async fn enrich_dead_letter(
+    clients: &HashMap<String, impl LambdaApi>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| fnc.dead_letter_target.is_none() && matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let Some(client) = clients.get(&fnc.arn) else {
+            continue;
+        };
+        let config = client.get_function(&fnc.arn).await?;
+        fnc.dead_letter_target =
+            dead_letter_target(config.as_ref().and_then(|c| c.dead_letter_config()));
+    }
+
+    Ok(())
+}
+
+/// Provisioned concurrency is configured per alias or version, so every config
+/// for the function is listed and their requested executions added up.
+async fn enrich_concurrency(
+    clients: &HashMap<String, impl LambdaApi>,
+    deployed_lambdas: &mut [Lambda],
+    matched: &[String],
+    shutdown: &Shutdown,
+) -> Result<(), anyhow::Error> {
+    for fnc in deployed_lambdas
+        .iter_mut()
+        .filter(|fnc| matched.contains(&fnc.arn))
+    {
+        if shutdown.requested() {
+            break;
+        }
+
+        let Some(client) = clients.get(&fnc.arn) else {
+            continue;
+        };
+        let mut next_marker: Option<String> = None;
+        let mut requested = 0;
+        loop {
+            let page = client
+                .list_provisioned_concurrency(&fnc.arn, next_marker.as_deref())
+                .await?;
+            requested += page.requested.iter().sum::<i32>();
+
+            match page.next_marker {
+                Some(marker) => next_marker = Some(marker),
+                None => break,
+            }
+        }
+        fnc.provisioned_concurrency = Some(requested);
+    }
+
+    Ok(())
+}
+
+/// The clients for one AWS target, reused for follow-up calls about the
+/// functions listed there.
+#[derive(Clone)]
+struct AwsClients {
+    lambda: Client,
+    ssm: aws_sdk_ssm::Client,
+}
+
+impl AwsClients {
+    fn new(config: &aws_config::SdkConfig) -> Self {
+        AwsClients {
+            lambda: Client::new(config),
+            ssm: aws_sdk_ssm::Client::new(config),
+        }
+    }
+}
+
+impl LambdaApi for AwsClients {
+    fn region(&self) -> Option<String> {
+        self.lambda.region()
+    }
+
+    async fn list_functions(&self, marker: Option<&str>) -> Result<FunctionPage, anyhow::Error> {
+        LambdaApi::list_functions(&self.lambda, marker).await
+    }
+
+    async fn get_function(
+        &self,
+        name: &str,
+    ) -> Result<Option<FunctionConfiguration>, anyhow::Error> {
+        LambdaApi::get_function(&self.lambda, name).await
+    }
+
+    async fn list_tags(&self, arn: &str) -> Result<HashMap<String, String>, anyhow::Error> {
+        LambdaApi::list_tags(&self.lambda, arn).await
+    }
+
+    async fn get_function_code_location(&self, name: &str) -> Result<CodeLocation, anyhow::Error> {
+        self.lambda.get_function_code_location(name).await
+    }
+
+    async fn list_provisioned_concurrency(
+        &self,
+        name: &str,
+        marker: Option<&str>,
+    ) -> Result<ConcurrencyPage, anyhow::Error> {
+        self.lambda.list_provisioned_concurrency(name, marker).await
+    }
+}
+
+impl SsmApi for AwsClients {
+    async fn get_parameter(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+        SsmApi::get_parameter(&self.ssm, name).await
+    }
+}
+
+/// Wraps an SDK error, marking rejected or missing credentials as an [`AuthError`].
+fn aws_error<E, R>(err: SdkError<E, R>) -> anyhow::Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    // Requests without credentials fail to construct before anything is sent.
+    let auth_failure = matches!(err, SdkError::ConstructionFailure(_))
+        || matches!(
+            err.code(),
+            Some(
+                "UnrecognizedClientException"
+                    | "InvalidSignatureException"
+                    | "ExpiredTokenException"
+                    | "AccessDeniedException"
+            )
+        );
+
+    if auth_failure {
+        AuthError(format!("AWS: {}", DisplayErrorContext(&err))).into()
+    } else {
+        anyhow::Error::new(err)
+    }
+}
+
+/// Narrows which deployed functions are considered.
+#[derive(Debug, Default)]
+pub struct LambdaFilter {
+    pub architecture: Option<Architecture>,
+    pub vpc_id: Option<String>,
+    /// Every one of these must be set on the function.
+    pub env: Vec<EnvFilter>,
+}
+
+/// With `checkpoint`, pages already fetched for that target are picked up from it,
+/// and the marker is saved after each page. Each page is redacted with `redactor`
+/// before it's saved.
+pub async fn get_deployed_lambdas_list(
+    client: &impl LambdaApi,
+    filter: &LambdaFilter,
+    redactor: Option<&redact::Redactor>,
+    checkpoint: Option<(&Checkpoint, &str)>,
+    shutdown: &Shutdown,
+) -> Result<Vec<Lambda>, anyhow::Error> {
+    let saved = checkpoint.and_then(|(checkpoint, target)| checkpoint.listing(target));
+    let resumed = saved.is_some();
+    let Listing {
+        mut next_marker,
+        mut total_functions,
+        functions: mut function_deets,
+    } = saved.unwrap_or(Listing {
+        next_marker: None,
+        total_functions: 0,
+        functions: Vec::new(),
+    });
+    let mut complete = resumed && next_marker.is_none();
+
+    while !complete {
+        if shutdown.requested() {
+            break;
+        }
+
+        let resp = client.list_functions(next_marker.as_deref()).await?;
+        let resp_functions = resp.functions;
+        total_functions += resp_functions.len();
+
+        let functions = resp_functions
+            .iter()
+            .map(|func| {
+                // Versions can also come from description or tags, so functions
+                // without env vars are kept too.
+                let env_vars = func
+                    .environment()
+                    .and_then(|environment| environment.variables())
+                    .cloned()
+                    .unwrap_or_default();
+                let name = func.function_name().unwrap().to_string();
+                let arn = func.function_arn().unwrap().to_string();
+                // Lambda omits the field for functions that predate Graviton support,
+                // and those are always x86_64.
+                let architectures = match func.architectures() {
+                    Some(archs) if !archs.is_empty() => {
+                        archs.iter().map(|a| a.as_str().to_string()).collect()
+                    }
+                    _ => vec![Architecture::X86_64.as_str().to_string()],
+                };
+                let (account_id, region, partition) = arn_location(&arn);
+                Lambda {
+                    name,
+                    env_vars,
+                    account_id,
+                    region,
+                    partition,
+                    arn,
+                    architectures,
+                    runtime: func.runtime().map(|runtime| runtime.as_str().to_string()),
+                    memory_size: func.memory_size(),
+                    timeout: func.timeout(),
+                    last_modified: func.last_modified().map(str::to_string),
+                    code_size: Some(func.code_size()),
+                    vpc: func.vpc_config().map(Vpc::from_config),
+                    dead_letter_target: dead_letter_target(func.dead_letter_config()),
+                    description: func
+                        .description()
+                        .filter(|description| !description.is_empty())
+                        .map(str::to_string),
+                    ..Default::default()
+                }
+            })
+            .filter(|fnc| match filter.architecture {
+                Some(arch) => fnc.architectures.iter().any(|a| a == arch.as_str()),
+                None => true,
+            })
+            .filter(|fnc| {
+                filter
+                    .env
+                    .iter()
+                    .all(|env| fnc.env_vars.get(&env.key) == Some(&env.value))
+            });
+
+        let start = function_deets.len();
+        function_deets.extend(functions);
+        if let Some(redactor) = redactor {
+            redactor.redact_all(&mut function_deets[start..]);
+        }
+
+        next_marker = resp.next_marker;
+        complete = next_marker.is_none();
+        if let Some((checkpoint, target)) = checkpoint {
+            let listing = Listing {
+                next_marker: next_marker.clone(),
+                total_functions,
+                functions: function_deets.clone(),
+            };
+            checkpoint.record_listing(target, listing);
+        }
+    }
+
+    if let Some(vpc_id) = &filter.vpc_id {
+        // The listing can leave out VPC config, so only in that case is it worth a
+        // get_function call per function, and only when the filter needs it.
+        for fnc in function_deets.iter_mut().filter(|fnc| fnc.vpc.is_none()) {
+            if shutdown.requested() {
+                break;
+            }
+
+            let config = client.get_function(&fnc.name).await?;
+            fnc.vpc = config
+                .as_ref()
+                .and_then(|config| config.vpc_config())
+                .map(Vpc::from_config);
+        }
+
+        function_deets.retain(|fnc| {
+            fnc.vpc
+                .as_ref()
+                .is_some_and(|vpc| vpc.vpc_id.as_deref() == Some(vpc_id.as_str()))
+        });
+    }
+
+    if total_functions > 0 {
+        progress!(
+            "Filtered {} function(s) down to {}",
+            total_functions,
+            function_deets.len()
+        );
+    } else if !shutdown.requested() {
+        let region = client.region().unwrap_or_else(|| "?".to_string());
+        eprintln!(
+            "No Lambda functions found in region {}; check the credentials and region",
+            region
+        );
+    }
+
+    Ok(function_deets)
+}
+
+/// Every unarchived repo `owner` has that `filter` keeps. `owner` is tried as an
+/// org first, which includes private repos the token can see, then as a user.
+async fn list_owner_repos(
+    octocrab: &Octocrab,
+    owner: &str,
+    filter: &repos::OrgFilter<'_>,
+) -> Result<Vec<Repo>, anyhow::Error> {
+    let first_page = match octocrab.orgs(owner).list_repos().per_page(100).send().await {
+        Err(octocrab::Error::GitHub { source, .. }) if source.message == "Not Found" => {
+            octocrab
+                .get(
+                    format!("/users/{}/repos", owner),
+                    Some(&[("per_page", 100)]),
+                )
+                .await
+        }
+        page => page,
+    };
+    let listed = match first_page {
+        Ok(page) => octocrab.all_pages(page).await,
+        Err(e) => Err(e),
+    };
+    let listed: Vec<octocrab::models::Repository> = match listed {
+        Ok(listed) => listed,
+        Err(octocrab::Error::GitHub { source, .. }) if source.message == "Bad credentials" => {
+            return Err(AuthError(format!("GitHub: {}", source.message)).into());
+        }
+        Err(e) => return Err(anyhow!("Failed to list the repos of {}: {}", owner, e)),
+    };
+
+    let repos: Vec<Repo> = listed
+        .into_iter()
+        .filter(|repo| repo.archived != Some(true))
+        .filter(|repo| {
+            filter.keeps(
+                &repo.name,
+                repo.topics.as_deref().unwrap_or_default(),
+                repo.language.as_ref().and_then(Value::as_str),
+            )
+        })
+        .map(|repo| Repo {
+            owner: owner.to_string(),
+            name: repo.name,
+            git_ref: None,
+            package: None,
+        })
+        .collect();
+    progress!("Found {} repos to scan in {}", repos.len(), owner);
+
+    Ok(repos)
+}
+
+/// Replaces each repo that has package.json files below its root with one entry
+/// per package. Repos already split into packages are kept as they are, and so
+/// are those whose tree can't be read, with a warning.
+async fn discover_packages(
+    github: &impl GitHubApi,
+    repos: Vec<Repo>,
+    options: FetchOptions<'_>,
+    concurrency: usize,
+) -> Vec<Repo> {
+    #[derive(Deserialize)]
+    struct Tree {
+        tree: Vec<TreeEntry>,
+        truncated: bool,
+    }
+    #[derive(Deserialize)]
+    struct TreeEntry {
+        path: String,
+    }
+
+    let discoveries = repos.into_iter().map(|repo| async move {
+        if repo.package.is_some() {
+            return vec![repo];
+        }
+
+        let git_ref = options.for_repo(&repo).git_ref.unwrap_or("HEAD");
+        let route = format!(
+            "/repos/{}/{}/git/trees/{}?recursive=1",
+            repo.owner,
+            repo.name,
+            query_escape(git_ref)
+        );
+        let tree = github_get(github, options.cache, &route, is_commit_sha(git_ref))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|body| serde_json::from_str::<Tree>(&body).map_err(|e| e.to_string()));
+        let tree = match tree {
+            Ok(tree) => tree,
+            Err(e) => {
+                warning!("failed to list the files of {}: {}", repo, e);
+                return vec![repo];
+            }
+        };
+        if tree.truncated {
+            warning!(
+                "{} has too many files for GitHub to list them all, some packages may be missed",
+                repo
+            );
+        }
+
+        let packages = repos::packages_in_tree(tree.tree.iter().map(|entry| entry.path.as_str()));
+        if packages.is_empty() {
+            return vec![repo];
+        }
+        progress!("Found {} package(s) in {}", packages.len(), repo);
+        repo.with_packages(&packages)
+    });
+
+    stream::iter(discoveries)
+        .buffered(concurrency)
+        .flat_map(stream::iter)
+        .collect()
+        .await
+}
+
+/// Fine-grained tokens are scoped to specific repos, and GitHub answers 404 for
+/// everything outside that scope. Probing each repo up front lets a repo the
+/// token can't see be reported as such, rather than as a missing package.json.
+async fn inaccessible_repos<'a>(
+    github: &impl GitHubApi,
+    repos: &'a [Repo],
+    cache: &ContentCache,
+    concurrency: usize,
+    shutdown: &Shutdown,
+) -> Result<HashSet<&'a Repo>, anyhow::Error> {
+    let probes = repos.iter().map(|repo| async move {
+        if shutdown.requested() {
+            return Ok(None);
+        }
+
+        let route = format!("/repos/{}/{}", repo.owner, repo.name);
+        match github_get(github, cache, &route, false).await {
+            Ok(_) => Ok(None),
+            Err(ApiError::GitHub { message }) if message == "Bad credentials" => {
+                Err(AuthError(format!("GitHub: {}", message)).into())
+            }
+            Err(ApiError::GitHub { message }) if message == "Not Found" => {
+                warning!(
+                    "the GitHub token cannot access {}, check its repository scope",
+                    repo
+                );
+                Ok(Some(repo))
+            }
+            Err(e) => {
+                warning!("could not verify access to repo {}: {}", repo, e);
+                Ok(None)
+            }
+        }
+    });
+
+    stream::iter(probes)
+        .buffer_unordered(concurrency)
+        .try_filter_map(future::ok)
+        .try_collect()
+        .await
+}
+
+/// Where in a repo the expected version is read from.
+pub enum Manifest {
+    /// The `version` field of the root package.json.
+    PackageJson,
+    /// The trimmed contents of a plain file, e.g. `deploy.lock` or `version.txt`.
+    RawFile(String),
+    /// The `version` field of a JSON asset on the latest release, e.g. `version.json`.
+    ReleaseAsset(String),
+}
+
+impl Manifest {
+    fn from_args(args: &Args) -> Self {
+        match (&args.release_asset, &args.version_file) {
+            (Some(name), _) => Manifest::ReleaseAsset(name.clone()),
+            (None, Some(path)) => Manifest::RawFile(path.clone()),
+            (None, None) => Manifest::PackageJson,
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            Manifest::PackageJson => "package.json",
+            Manifest::RawFile(path) | Manifest::ReleaseAsset(path) => path,
+        }
+    }
+
+    /// `e`, with which repo, ref, and file it happened for.
+    fn fetch_error(&self, repo: &Repo, options: FetchOptions, e: &anyhow::Error) -> FetchError {
+        let path = repo.file_path(self.path());
+        let (owner, repo, message) = (repo.owner.clone(), repo.name.clone(), e.to_string());
+        match self {
+            Manifest::ReleaseAsset(asset) => FetchError::ReleaseAsset {
+                owner,
+                repo,
+                asset: asset.clone(),
+                message,
+            },
+            _ => FetchError::File {
+                owner,
+                repo,
+                git_ref: options.git_ref.map(str::to_string),
+                path,
+                message,
+            },
+        }
+    }
+
+    /// Where the expected version came from, as shown in the report.
+    fn label(&self) -> String {
+        match self {
+            Manifest::ReleaseAsset(name) => format!("{} (latest release)", name),
+            other => other.path().to_string(),
+        }
+    }
+}
+
+/// How repo files are read.
+#[derive(Clone, Copy)]
+pub struct FetchOptions<'a> {
+    /// Branch, tag, or SHA to read at, instead of the default branch.
+    pub git_ref: Option<&'a str>,
+    /// Read at each repo's latest release tag instead of `git_ref`.
+    pub latest_release: bool,
+    /// Files larger than this many bytes are refused rather than decoded.
+    pub max_file_size: u64,
+    pub cache: &'a ContentCache,
+}
+
+impl<'a> FetchOptions<'a> {
+    /// Falls back to the ref configured for `repo` when none was given.
+    fn for_repo<'r>(self, repo: &'r Repo) -> FetchOptions<'r>
+    where
+        'a: 'r,
+    {
+        FetchOptions {
+            git_ref: self.git_ref.or(repo.git_ref.as_deref()),
+            ..self
+        }
+    }
+}
+
+/// Refuses `path` when GitHub reports it as larger than `max_file_size`.
+fn check_file_size(path: &str, size: i64, max_file_size: u64) -> Result<(), anyhow::Error> {
+    if u64::try_from(size).is_ok_and(|size| size > max_file_size) {
+        return Err(anyhow!(
+            "{} is {} bytes, over the {} byte limit (see --max-file-size)",
+            path,
+            size,
+            max_file_size
+        ));
+    }
+    Ok(())
+}
+
+/// Reads up to `concurrency` repos at a time. A repo that fails is recorded as
+/// such and doesn't stop the others.
+pub async fn fetch_packagejson_details(
+    github: &impl GitHubApi,
+    repos: &[Repo],
+    manifest: &Manifest,
+    options: FetchOptions<'_>,
+    concurrency: usize,
+    checkpoint: Option<&Checkpoint>,
+    shutdown: &Shutdown,
+) -> Result<BTreeMap<std::string::String, RepoDetails>, anyhow::Error> {
+    let saved = |repo: &Repo| checkpoint.and_then(|checkpoint| checkpoint.repo(&repo.to_string()));
+    let unread: Vec<Repo> = repos
+        .iter()
+        .filter(|repo| saved(repo).is_none())
+        .cloned()
+        .collect();
+    let inaccessible =
+        inaccessible_repos(github, &unread, options.cache, concurrency, shutdown).await?;
+
+    let fetches = repos.iter().map(|repo| {
+        let (inaccessible, saved) = (&inaccessible, &saved);
+        async move {
+            if shutdown.requested() {
+                return None;
+            }
+
+            if let Some(details) = saved(repo) {
+                return Some((repo.key().to_string(), details));
+            }
+
+            if inaccessible.contains(repo) {
+                let details = RepoDetails {
+                    version: Err(FetchError::NoAccess {
+                        owner: repo.owner.clone(),
+                        repo: repo.name.clone(),
+                    }),
+                    package_name: None,
+                    commit: None,
+                    elapsed: Duration::ZERO,
+                };
+                return Some((repo.key().to_string(), details));
+            }
+
+            let options = options.for_repo(repo);
+            let started = Instant::now();
+            let fetched = async {
+                let tag = release_tag(github, repo, options).await?;
+                let options = FetchOptions {
+                    git_ref: tag.as_deref().or(options.git_ref),
+                    ..options
+                };
+                // Reading at the resolved SHA rather than the ref means the commit
+                // reported is exactly the one the file came from, even if the ref moves.
+                let commit = match manifest {
+                    Manifest::ReleaseAsset(_) => None,
+                    _ => Some(head_commit(github, repo, options).await?),
+                };
+                let pinned = FetchOptions {
+                    git_ref: commit.as_deref().or(options.git_ref),
+                    ..options
+                };
+                let (version, package_name) =
+                    get_expected_version(github, repo, manifest, pinned).await?;
+                Ok::<_, anyhow::Error>((version, package_name, commit))
+            }
+            .await;
+
+            let (version, package_name, commit) = match fetched {
+                Ok((version, package_name, commit)) => (Ok(version), package_name, commit),
+                Err(e) => {
+                    warning!("failed to get {} for repo {}: {}", manifest.path(), repo, e);
+                    (Err(manifest.fetch_error(repo, options, &e)), None, None)
+                }
+            };
+
+            let details = RepoDetails {
+                version,
+                package_name,
+                commit,
+                elapsed: started.elapsed(),
+            };
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.record_repo(&repo.to_string(), &details);
+            }
+            Some((repo.key().to_string(), details))
+        }
+    });
+
+    Ok(stream::iter(fetches)
+        .buffer_unordered(concurrency)
+        .filter_map(future::ready)
+        .collect()
+        .await)
+}
+
+/// The SHA the ref (or the default branch) currently points at.
+async fn head_commit(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    options: FetchOptions<'_>,
+) -> Result<String, anyhow::Error> {
+    #[derive(Deserialize)]
+    struct Commit {
+        sha: String,
+    }
+
+    let mut route = format!("/repos/{}/{}/commits?per_page=1", repo.owner, repo.name);
+    if let Some(git_ref) = options.git_ref {
+        route = format!("{}&sha={}", route, query_escape(git_ref));
+    }
+
+    let target = options.git_ref.unwrap_or("the default branch");
+    let body = github_get(github, options.cache, &route, false)
+        .await
+        .map_err(|e| anyhow!("Failed to resolve {}: {}", target, e))?;
+    let commits: Vec<Commit> =
+        serde_json::from_str(&body).map_err(|e| anyhow!("Failed to resolve {}: {}", target, e))?;
+    commits
+        .into_iter()
+        .next()
+        .map(|commit| commit.sha)
+        .ok_or_else(|| anyhow!("{} has no commits", target))
+}
+
+/// With `--latest-release`, the tag of the repo's latest release.
+async fn release_tag(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    options: FetchOptions<'_>,
+) -> Result<Option<String>, anyhow::Error> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+
+    if !options.latest_release {
+        return Ok(None);
+    }
+    let route = format!("/repos/{}/{}/releases/latest", repo.owner, repo.name);
+    let body = github_get(github, options.cache, &route, false)
+        .await
+        .map_err(|e| anyhow!("Failed to get latest release: {}", e))?;
+    let release: Release = serde_json::from_str(&body)
+        .map_err(|e| anyhow!("Failed to parse latest release: {}", e))?;
+    Ok(Some(release.tag_name))
+}
+
+/// GETs `route` from the GitHub API, going through `cache`. A cached response
+/// older than the TTL is revalidated by its ETag, unless `immutable` says the
+/// route can't change, as with file content at a commit SHA.
+async fn github_get(
+    github: &impl GitHubApi,
+    cache: &ContentCache,
+    route: &str,
+    immutable: bool,
+) -> Result<String, ApiError> {
+    let cached = cache.get(route);
+    let etag = match &cached {
+        Some((response, fresh)) if *fresh || immutable => return Ok(response.body.clone()),
+        Some((response, _)) => response.etag.as_deref(),
+        None => None,
+    };
+
+    match github.get(route, etag).await? {
+        Fetched::NotModified => match cached {
+            Some((cached, _)) => {
+                cache.revalidated(route);
+                Ok(cached.body)
+            }
+            // Only possible if GitHub ignored that there was no ETag to match.
+            None => Err(ApiError::Status(StatusCode::NOT_MODIFIED)),
+        },
+        Fetched::Body { etag, body } => {
+            cache.put(route, etag, body.clone());
+            Ok(body)
+        }
+    }
+}
+
+/// Percent-encodes `value` for a query string, leaving `/` as is since refs
+/// such as `release/1.2` are common.
+fn query_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Whether `git_ref` is a full commit SHA, which always names the same content.
+fn is_commit_sha(git_ref: &str) -> bool {
+    git_ref.len() == 40 && git_ref.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Fetches every repo's full package.json, skipping repos that fail, along with
+/// how many did.
+async fn fetch_packagejsons(
+    github: &impl GitHubApi,
+    repos: &[Repo],
+    options: FetchOptions<'_>,
+    concurrency: usize,
+    shutdown: &Shutdown,
+) -> Result<(BTreeMap<String, HashMap<String, Value>>, usize), anyhow::Error> {
+    let inaccessible =
+        inaccessible_repos(github, repos, options.cache, concurrency, shutdown).await?;
+
+    let fetches = repos
+        .iter()
+        .filter(|repo| !inaccessible.contains(repo))
+        .map(|repo| async move {
+            if shutdown.requested() {
+                return None;
+            }
+            let options = options.for_repo(repo);
+            let package_json = async {
+                let tag = release_tag(github, repo, options).await?;
+                let options = FetchOptions {
+                    git_ref: tag.as_deref().or(options.git_ref),
+                    ..options
+                };
+                get_packagejson(github, repo, options).await
+            };
+            match package_json.await {
+                Ok(package_json) => Some(Some((repo.key().to_string(), package_json))),
+                Err(e) => {
+                    warning!("failed to get package.json for repo {}: {}", repo, e);
+                    Some(None)
+                }
+            }
+        });
+
+    let fetched: Vec<_> = stream::iter(fetches)
+        .buffer_unordered(concurrency)
+        .filter_map(future::ready)
+        .collect()
+        .await;
+    let failed = inaccessible.len() + fetched.iter().filter(|fetched| fetched.is_none()).count();
+    Ok((fetched.into_iter().flatten().collect(), failed))
+}
+
+async fn get_expected_version(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    manifest: &Manifest,
+    options: FetchOptions<'_>,
+) -> Result<(Option<Value>, Option<String>), anyhow::Error> {
+    match manifest {
+        Manifest::PackageJson => {
+            let package_json = get_packagejson(github, repo, options).await?;
+            let package_name = package_json
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            Ok((package_json.get("version").cloned(), package_name))
+        }
+        Manifest::RawFile(path) => {
+            let content = get_file_content(github, repo, &repo.file_path(path), options).await?;
+            let version = content.trim();
+            let version = (!version.is_empty()).then(|| Value::String(version.to_string()));
+            Ok((version, None))
+        }
+        Manifest::ReleaseAsset(name) => {
+            let content = get_release_asset(github, repo, name, options).await?;
+            let asset: HashMap<String, Value> = serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", name, e))?;
+            Ok((asset.get("version").cloned(), None))
+        }
+    }
+}
+
+/// Downloads the named asset of the repo's latest release.
+async fn get_release_asset(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    name: &str,
+    options: FetchOptions<'_>,
+) -> Result<String, anyhow::Error> {
+    let route = format!("/repos/{}/{}/releases/latest", repo.owner, repo.name);
+    let body = github_get(github, options.cache, &route, false)
+        .await
+        .map_err(|e| anyhow!("Failed to get latest release: {}", e))?;
+    let release: Release = serde_json::from_str(&body)
+        .map_err(|e| anyhow!("Failed to parse latest release: {}", e))?;
+
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| anyhow!("Release {} has no asset {}", release.tag_name, name))?;
+    check_file_size(name, asset.size, options.max_file_size)?;
+
+    github
+        .download_asset(asset.url.as_str())
+        .await
+        .map_err(|e| anyhow!("Failed to download {}: {}", name, e))
+}
+
+async fn get_packagejson(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    options: FetchOptions<'_>,
+) -> Result<HashMap<String, Value>, anyhow::Error> {
+    let path = repo.file_path("package.json");
+    let package_json_content = get_file_content(github, repo, &path, options).await?;
+
+    let package_json_deserialized: HashMap<String, Value> =
+        serde_json::from_str(&package_json_content)
+            .map_err(|e| anyhow!("Failed to parse package.json: {}", e))?;
+
+    Ok(package_json_deserialized)
+}
+
+async fn get_file_content(
+    github: &impl GitHubApi,
+    repo: &Repo,
+    path: &str,
+    options: FetchOptions<'_>,
+) -> Result<String, anyhow::Error> {
+    let requested = path.trim_start_matches('/');
+    let mut route = format!("/repos/{}/{}/contents/{}", repo.owner, repo.name, requested);
+    if let Some(git_ref) = options.git_ref {
+        route = format!("{}?ref={}", route, query_escape(git_ref));
+    }
+
+    let pinned = options.git_ref.is_some_and(is_commit_sha);
+    let body = github_get(github, options.cache, &route, pinned)
+        .await
+        .map_err(|e| anyhow!("Failed to get {} content: {}", path, e))?;
+
+    // A directory path comes back as a listing of its children, so only an item
+    // that is the requested path itself, and is a file, can be decoded.
+    let items: Vec<Content> = match serde_json::from_str(&body) {
+        Ok(Value::Array(items)) => serde_json::from_value(Value::Array(items)),
+        Ok(item) => serde_json::from_value(item).map(|item| vec![item]),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| anyhow!("Failed to parse {} content: {}", path, e))?;
+    let item = match items.iter().find(|item| item.path == requested) {
+        Some(item) if item.r#type == "file" => item,
+        Some(item) => return Err(anyhow!("{} is a {}, not a file", path, item.r#type)),
+        None if items.is_empty() => return Err(anyhow!("{} content not found", path)),
+        None => return Err(anyhow!("{} is a directory, not a file", path)),
+    };
+
+    check_file_size(path, item.size, options.max_file_size)?;
+
+    item.decoded_content()
+        .ok_or_else(|| anyhow!("Failed to decode {} content", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_lambda::types::EnvironmentResponse;
+    use compare::VersionSource;
+    use std::sync::OnceLock;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    /// Scraper's package.json at version 1.2.0, as the contents API returns it.
+    const PACKAGE_JSON: &str = r#"{
+        "name": "package.json",
+        "path": "package.json",
+        "sha": "f00d",
+        "encoding": "base64",
+        "content": "eyJuYW1lIjogInNjcmFwZXIiLCAidmVyc2lvbiI6ICIxLjIuMCJ9",
+        "size": 39,
+        "url": "https://api.github.com/repos/Mikeysauce/scraper/contents/package.json",
+        "type": "file",
+        "_links": { "self": "https://api.github.com/repos/Mikeysauce/scraper/contents/package.json" }
+    }"#;
+
+    /// One entry of a contents response, holding `content` base64-encoded.
+    fn content_item(path: &str, r#type: &str, content: &str) -> Value {
+        serde_json::json!({
+            "name": path.rsplit('/').next().unwrap(),
+            "path": path,
+            "sha": "f00d",
+            "encoding": "base64",
+            "content": content,
+            "size": 6,
+            "url": format!("https://api.github.com/repos/Mikeysauce/scraper/contents/{}", path),
+            "type": r#type,
+            "_links": { "self": format!("https://api.github.com/repos/Mikeysauce/scraper/contents/{}", path) }
+        })
+    }
+
+    /// Answers each route with a canned body, and anything else as GitHub does
+    /// for a repo the token can't see.
+    struct FakeGitHub(HashMap<String, String>);
+
+    impl GitHubApi for FakeGitHub {
+        async fn get(&self, route: &str, _etag: Option<&str>) -> Result<Fetched, ApiError> {
+            match self.0.get(route) {
+                Some(body) => Ok(Fetched::Body {
+                    etag: None,
+                    body: body.clone(),
+                }),
+                None => Err(ApiError::GitHub {
+                    message: "Not Found".to_string(),
+                }),
+            }
+        }
+
+        async fn download_asset(&self, _url: &str) -> Result<String, ApiError> {
+            Err(ApiError::Status(StatusCode::NOT_FOUND))
+        }
+    }
+
+    /// Lists one function per page.
+    struct FakeLambda(Vec<FunctionConfiguration>);
+
+    impl LambdaApi for FakeLambda {
+        fn region(&self) -> Option<String> {
+            Some("eu-west-1".to_string())
+        }
+
+        async fn list_functions(
+            &self,
+            marker: Option<&str>,
+        ) -> Result<FunctionPage, anyhow::Error> {
+            let page: usize = marker.map_or(0, |marker| marker.parse().unwrap());
+            Ok(FunctionPage {
+                functions: self.0.get(page).cloned().into_iter().collect(),
+                next_marker: (page + 1 < self.0.len()).then(|| (page + 1).to_string()),
+            })
+        }
+
+        async fn get_function(
+            &self,
+            name: &str,
+        ) -> Result<Option<FunctionConfiguration>, anyhow::Error> {
+            Ok(self
+                .0
+                .iter()
+                .find(|fnc| fnc.function_name() == Some(name) || fnc.function_arn() == Some(name))
+                .cloned())
+        }
+
+        /// Each function is tagged with its own name as `service`.
+        async fn list_tags(&self, arn: &str) -> Result<HashMap<String, String>, anyhow::Error> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|fnc| fnc.function_arn() == Some(arn))
+                .filter_map(|fnc| fnc.function_name())
+                .map(|name| ("service".to_string(), name.to_string()))
+                .collect())
+        }
+
+        /// Every function is a container image, so has no package to download.
+        async fn get_function_code_location(
+            &self,
+            _name: &str,
+        ) -> Result<CodeLocation, anyhow::Error> {
+            Ok(CodeLocation {
+                code_size: 0,
+                url: None,
+            })
+        }
+
+        /// Each function has its position in the listing, plus one, provisioned,
+        /// split into one execution per page.
+        async fn list_provisioned_concurrency(
+            &self,
+            name: &str,
+            marker: Option<&str>,
+        ) -> Result<ConcurrencyPage, anyhow::Error> {
+            let total = self
+                .0
+                .iter()
+                .position(|fnc| fnc.function_arn() == Some(name))
+                .map_or(0, |position| position + 1);
+            let page: usize = marker.map_or(0, |marker| marker.parse().unwrap());
+            Ok(ConcurrencyPage {
+                requested: if total == 0 { Vec::new() } else { vec![1] },
+                next_marker: (page + 1 < total).then(|| (page + 1).to_string()),
+            })
+        }
+    }
+
+    /// Answers each parameter name with its value.
+    struct FakeSsm(HashMap<String, String>);
+
+    impl SsmApi for FakeSsm {
+        async fn get_parameter(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+            Ok(self.0.get(name).cloned())
+        }
+    }
+
+    /// Reads at the default branch, with caching off.
+    fn fetch_options() -> FetchOptions<'static> {
+        static CACHE: OnceLock<ContentCache> = OnceLock::new();
+        FetchOptions {
+            git_ref: None,
+            latest_release: false,
+            max_file_size: 1024,
+            cache: CACHE.get_or_init(|| ContentCache::new(Duration::ZERO, false, "test")),
+        }
+    }
+
+    fn function(name: &str, version: Option<&str>) -> FunctionConfiguration {
+        let mut function = FunctionConfiguration::builder()
+            .function_name(name)
+            .function_arn(format!(
+                "arn:aws:lambda:eu-west-1:123456789012:function:{}",
+                name
+            ));
+        if let Some(version) = version {
+            function = function.environment(
+                EnvironmentResponse::builder()
+                    .variables("APP_VERSION", version)
+                    .build(),
+            );
+        }
+        function.build()
+    }
+
+    #[tokio::test]
+    async fn compares_repos_against_functions_without_live_services() {
+        let github = FakeGitHub(HashMap::from([
+            ("/repos/Mikeysauce/scraper".to_string(), "{}".to_string()),
+            (
+                "/repos/Mikeysauce/scraper/commits?per_page=1".to_string(),
+                format!(r#"[{{ "sha": "{}" }}]"#, SHA),
+            ),
+            (
+                format!(
+                    "/repos/Mikeysauce/scraper/contents/package.json?ref={}",
+                    SHA
+                ),
+                PACKAGE_JSON.to_string(),
+            ),
+        ]));
+        let repos = repos::parse_repo_list("scraper\nmovies-front\n", "Mikeysauce").unwrap();
+        let options = fetch_options();
+        let shutdown = Shutdown::default();
+
+        let details = fetch_packagejson_details(
+            &github,
+            &repos,
+            &Manifest::PackageJson,
+            options,
+            2,
+            None,
+            &shutdown,
+        )
+        .await
+        .unwrap();
+        let scraper = &details["scraper"];
+        assert_eq!(
+            scraper.version.as_ref().unwrap(),
+            &Some(Value::from("1.2.0"))
+        );
+        assert_eq!(scraper.commit.as_deref(), Some(SHA));
+        assert!(matches!(
+            details["movies-front"].version,
+            Err(FetchError::NoAccess { .. })
+        ));
+
+        let lambda = FakeLambda(vec![
+            function("scraper-prod", Some("1.0.0")),
+            function("no-env", None),
+        ]);
+        let filter = LambdaFilter::default();
+        let functions = get_deployed_lambdas_list(&lambda, &filter, None, None, &shutdown)
+            .await
+            .unwrap();
+        assert_eq!(functions.len(), 2);
+        assert!(functions[1].env_vars.is_empty());
+
+        let matcher = Matcher::default();
+        let deployed_version =
+            DeployedVersion::new(VersionSource::Env("APP_VERSION".to_string()), None);
+        let comparisons = compare::compare(&details, &functions, &deployed_version, &matcher);
+        let statuses: Vec<_> = comparisons
+            .iter()
+            .map(|comparison| (comparison.repo, comparison.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [("movies-front", Status::Error), ("scraper", Status::Drift)]
+        );
+    }
+
+    #[tokio::test]
+    async fn package_jsons_that_fail_are_counted() {
+        let github = FakeGitHub(HashMap::from([
+            ("/repos/Mikeysauce/scraper".to_string(), "{}".to_string()),
+            (
+                "/repos/Mikeysauce/standen-node".to_string(),
+                "{}".to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/scraper/contents/package.json".to_string(),
+                PACKAGE_JSON.to_string(),
+            ),
+        ]));
+        let repos =
+            repos::parse_repo_list("scraper\nmovies-front\nstanden-node\n", "Mikeysauce").unwrap();
+        let options = fetch_options();
+
+        let (package_jsons, failed) =
+            fetch_packagejsons(&github, &repos, options, 2, &Shutdown::default())
+                .await
+                .unwrap();
+        assert_eq!(package_jsons.keys().collect::<Vec<_>>(), ["scraper"]);
+        // movies-front can't be seen and standen-node has no package.json.
+        assert_eq!(failed, 2);
+    }
+
+    #[tokio::test]
+    async fn latest_release_reads_files_at_the_release_tag() {
+        let github = FakeGitHub(HashMap::from([
+            ("/repos/Mikeysauce/scraper".to_string(), "{}".to_string()),
+            (
+                "/repos/Mikeysauce/movies-front".to_string(),
+                "{}".to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/scraper/releases/latest".to_string(),
+                r#"{ "tag_name": "v1.2.0" }"#.to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/scraper/commits?per_page=1&sha=v1.2.0".to_string(),
+                format!(r#"[{{ "sha": "{}" }}]"#, SHA),
+            ),
+            (
+                format!(
+                    "/repos/Mikeysauce/scraper/contents/package.json?ref={}",
+                    SHA
+                ),
+                PACKAGE_JSON.to_string(),
+            ),
+        ]));
+        let repos = repos::parse_repo_list("scraper\nmovies-front\n", "Mikeysauce").unwrap();
+        let options = FetchOptions {
+            latest_release: true,
+            ..fetch_options()
+        };
+
+        let details = fetch_packagejson_details(
+            &github,
+            &repos,
+            &Manifest::PackageJson,
+            options,
+            2,
+            None,
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            details["scraper"].version.as_ref().unwrap(),
+            &Some(Value::from("1.2.0"))
+        );
+        assert_eq!(details["scraper"].commit.as_deref(), Some(SHA));
+        // Without a release there's nothing to read, rather than the default branch.
+        assert!(details["movies-front"].version.is_err());
+        assert_eq!(details["movies-front"].commit, None);
+    }
+
+    #[tokio::test]
+    async fn only_the_requested_file_is_decoded() {
+        let contents = |route: &str| format!("/repos/Mikeysauce/scraper/contents/{}", route);
+        let github = FakeGitHub(HashMap::from([
+            (
+                contents("deploy"),
+                serde_json::json!([
+                    content_item("deploy/version.txt", "file", "MS40LjIK"),
+                    content_item("deploy/lib", "dir", ""),
+                ])
+                .to_string(),
+            ),
+            (
+                contents("deploy/version.txt"),
+                serde_json::json!([content_item("deploy/version.txt", "file", "MS40LjIK")])
+                    .to_string(),
+            ),
+            (contents("empty"), "[]".to_string()),
+        ]));
+        let repo = &repos::parse_repo_list("scraper", "Mikeysauce").unwrap()[0];
+        let options = fetch_options();
+        let read = |path: &'static str| get_file_content(&github, repo, path, options);
+
+        assert_eq!(read("deploy/version.txt").await.unwrap(), "1.4.2\n");
+        let error = read("deploy").await.unwrap_err();
+        assert_eq!(error.to_string(), "deploy is a directory, not a file");
+        let error = read("empty").await.unwrap_err();
+        assert_eq!(error.to_string(), "empty content not found");
+    }
+
+    #[tokio::test]
+    async fn version_file_holds_a_bare_version() {
+        let github = FakeGitHub(HashMap::from([
+            ("/repos/Mikeysauce/scraper".to_string(), "{}".to_string()),
+            (
+                "/repos/Mikeysauce/movies-front".to_string(),
+                "{}".to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/standen-node".to_string(),
+                "{}".to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/standen-node/commits?per_page=1".to_string(),
+                format!(r#"[{{ "sha": "{}" }}]"#, SHA),
+            ),
+            (
+                format!(
+                    "/repos/Mikeysauce/standen-node/contents/version.txt?ref={}",
+                    SHA
+                ),
+                // " \n"
+                content_item("version.txt", "file", "IAo=").to_string(),
+            ),
+            (
+                "/repos/Mikeysauce/scraper/commits?per_page=1".to_string(),
+                format!(r#"[{{ "sha": "{}" }}]"#, SHA),
+            ),
+            (
+                "/repos/Mikeysauce/movies-front/commits?per_page=1".to_string(),
+                format!(r#"[{{ "sha": "{}" }}]"#, SHA),
+            ),
+            (
+                format!("/repos/Mikeysauce/scraper/contents/version.txt?ref={}", SHA),
+                // "1.4.2\n"
+                content_item("version.txt", "file", "MS40LjIK").to_string(),
+            ),
+        ]));
+        let repos =
+            repos::parse_repo_list("scraper\nmovies-front\nstanden-node\n", "Mikeysauce").unwrap();
+
+        let details = fetch_packagejson_details(
+            &github,
+            &repos,
+            &Manifest::RawFile("version.txt".to_string()),
+            fetch_options(),
+            2,
+            None,
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            details["scraper"].version.as_ref().unwrap(),
+            &Some(Value::from("1.4.2"))
+        );
+        // A repo without the file failed to fetch, rather than having no version.
+        assert!(matches!(
+            &details["movies-front"].version,
+            Err(FetchError::File { path, .. }) if path == "version.txt"
+        ));
+        assert_eq!(details["standen-node"].version.as_ref().unwrap(), &None);
+    }
+
+    #[tokio::test]
+    async fn ssm_versions_are_read_for_matched_functions() {
+        let listed = |name: &str| Lambda {
+            name: name.to_string(),
+            arn: format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name),
+            ..Default::default()
+        };
+        let mut deployed_lambdas = vec![
+            listed("scraper-prod"),
+            listed("movies-front-prod"),
+            listed("standen-node-prod"),
+        ];
+        let ssm = || {
+            FakeSsm(HashMap::from([
+                (
+                    "/apps/scraper-prod/version".to_string(),
+                    "1.2.0".to_string(),
+                ),
+                (
+                    "/apps/standen-node-prod/version".to_string(),
+                    "0.3.1".to_string(),
+                ),
+            ]))
+        };
+        let clients: HashMap<_, _> = deployed_lambdas
+            .iter()
+            .map(|fnc| (fnc.arn.clone(), ssm()))
+            .collect();
+        let matched: Vec<String> = deployed_lambdas[..2]
+            .iter()
+            .map(|fnc| fnc.arn.clone())
+            .collect();
+
+        enrich_ssm_version(
+            "/apps/{function}/version",
+            &clients,
+            &mut deployed_lambdas,
+            &matched,
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        let versions: Vec<_> = deployed_lambdas
+            .iter()
+            .map(|fnc| fnc.ssm_version.as_deref())
+            .collect();
+        // movies-front-prod has no parameter, and standen-node-prod isn't matched.
+        assert_eq!(versions, [Some("1.2.0"), None, None]);
+    }
+
+    #[tokio::test]
+    async fn tags_are_read_through_lambda_api() {
+        let lambda = FakeLambda(vec![function("scraper-prod", None)]);
+        let arn = lambda.0[0].function_arn().unwrap().to_string();
+        let mut deployed_lambdas = vec![Lambda {
+            name: "scraper-prod".to_string(),
+            arn: arn.clone(),
+            ..Default::default()
+        }];
+        let clients = HashMap::from([(arn.clone(), lambda)]);
+
+        enrich_tags(
+            &clients,
+            &mut deployed_lambdas,
+            &[arn],
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(deployed_lambdas[0].tags["service"], "scraper-prod");
+    }
+
+    #[tokio::test]
+    async fn dead_letter_config_is_reread_for_matched_functions_only() {
+        let arn = |name: &str| format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name);
+        let with_queue = |name: &str| {
+            let mut function = function(name, None);
+            function.dead_letter_config = Some(
+                DeadLetterConfig::builder()
+                    .target_arn(format!("arn:aws:sqs:eu-west-1:123456789012:{}-dlq", name))
+                    .build(),
+            );
+            function
+        };
+        let functions = vec![
+            with_queue("scraper-prod"),
+            with_queue("movies-front-prod"),
+            function("standen-node-prod", None),
+        ];
+        let names = ["scraper-prod", "movies-front-prod", "standen-node-prod"];
+        let clients: HashMap<_, _> = names
+            .into_iter()
+            .map(|name| (arn(name), FakeLambda(functions.clone())))
+            .collect();
+        let listed = |name: &str| Lambda {
+            name: name.to_string(),
+            arn: arn(name),
+            ..Default::default()
+        };
+        let mut deployed_lambdas: Vec<_> = names.into_iter().map(listed).collect();
+        let matched = vec![arn("scraper-prod"), arn("standen-node-prod")];
+
+        enrich_dead_letter(
+            &clients,
+            &mut deployed_lambdas,
+            &matched,
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        let targets: Vec<_> = deployed_lambdas
+            .iter()
+            .map(|fnc| fnc.dead_letter_target.as_deref())
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                Some("arn:aws:sqs:eu-west-1:123456789012:scraper-prod-dlq"),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn provisioned_concurrency_is_summed_across_pages() {
+        let lambda = FakeLambda(vec![
+            function("scraper-prod", None),
+            function("movies-front-prod", None),
+            function("standen-node-prod", None),
+        ]);
+        let mut deployed_lambdas: Vec<Lambda> = lambda
+            .0
+            .iter()
+            .map(|fnc| Lambda {
+                name: fnc.function_name().unwrap().to_string(),
+                arn: fnc.function_arn().unwrap().to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let matched: Vec<String> = deployed_lambdas[1..]
+            .iter()
+            .map(|fnc| fnc.arn.clone())
+            .collect();
+        let clients: HashMap<_, _> = lambda
+            .0
+            .iter()
+            .map(|fnc| {
+                (
+                    fnc.function_arn().unwrap().to_string(),
+                    FakeLambda(lambda.0.clone()),
+                )
+            })
+            .collect();
+
+        enrich_concurrency(
+            &clients,
+            &mut deployed_lambdas,
+            &matched,
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        let requested: Vec<_> = deployed_lambdas
+            .iter()
+            .map(|fnc| fnc.provisioned_concurrency)
+            .collect();
+        assert_eq!(requested, [None, Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn assumed_roles_without_a_region_are_listed_in_every_region() {
+        const DEV: &str = "arn:aws:iam::111111111111:role/beacon";
+        const PROD: &str = "arn:aws:iam::222222222222:role/beacon";
+        let regions = [Some("eu-west-1".to_string()), Some("us-east-1".to_string())];
+        let region = |name: &str| Some(name.to_string());
+
+        let assume_role: Vec<AssumeRole> = [DEV.to_string(), format!("{}@ap-south-1", PROD)]
+            .iter()
+            .map(|arg| arg.parse().unwrap())
+            .collect();
+        assert_eq!(
+            listing_targets(&assume_role, None, &regions),
+            [
+                (Some(DEV), region("eu-west-1")),
+                (Some(DEV), region("us-east-1")),
+                (Some(PROD), region("ap-south-1")),
+            ]
+        );
+
+        assert_eq!(
+            listing_targets(&[], Some(DEV), &[None]),
+            [(Some(DEV), None)]
+        );
+        assert!("111111111111@eu-west-1".parse::<AssumeRole>().is_err());
+        assert!(format!("{}@", DEV).parse::<AssumeRole>().is_err());
+    }
+
+    #[tokio::test]
+    async fn endpoint_url_survives_assuming_a_role() {
+        let endpoint_url = Some("http://localhost:4566");
+        let region = || Some("eu-west-1".to_string());
+        let role_arn = Some("arn:aws:iam::111111111111:role/beacon");
+
+        let config = load_aws_config(region(), None, None, endpoint_url).await;
+        assert_eq!(config.endpoint_url(), endpoint_url);
+        let config = load_aws_config(region(), None, role_arn, endpoint_url).await;
+        assert_eq!(config.endpoint_url(), endpoint_url);
+        let config = load_aws_config(region(), None, role_arn, None).await;
+        assert_eq!(config.endpoint_url(), None);
+    }
+
+    #[test]
+    fn version_endpoints_must_answer_with_a_version_field() {
+        assert_eq!(
+            parse_http_version(r#"{"version": "1.2.0", "status": "ok"}"#).unwrap(),
+            "1.2.0"
+        );
+        let error = parse_http_version(r#"{"status": "ok"}"#).unwrap_err();
+        assert_eq!(error.to_string(), "response has no `version` field");
+        let error = parse_http_version("<html><body>Bad gateway</body></html>").unwrap_err();
+        assert!(
+            error.to_string().starts_with("response isn't JSON"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn listings_from_different_accounts_are_told_apart() {
+        let region = Region::new("eu-west-1");
+        let first = target_label(Some("111111111111"), None, None, None, &region);
+        let second = target_label(Some("222222222222"), None, None, None, &region);
+        assert_eq!(first, "default@eu-west-1 in 111111111111");
+        assert_ne!(first, second);
+        assert_eq!(
+            target_label(
+                None,
+                Some("dev"),
+                None,
+                Some("http://localhost:4566"),
+                &region
+            ),
+            "dev@eu-west-1 via http://localhost:4566"
+        );
+    }
+
+    #[tokio::test]
+    async fn functions_without_env_vars_are_listed() {
+        let mut described = function("scraper-prod", None);
+        described.description = Some("deployed-version: 1.2.0".to_string());
+        let mut empty = function("movies-front-prod", None);
+        empty.environment = Some(EnvironmentResponse::builder().build());
+        let lambda = FakeLambda(vec![described, empty]);
+        let filter = LambdaFilter::default();
+
+        let functions =
+            get_deployed_lambdas_list(&lambda, &filter, None, None, &Shutdown::default())
+                .await
+                .unwrap();
+        assert_eq!(functions.len(), 2);
+        let source = DeployedVersion::new(VersionSource::Description, None);
+        assert_eq!(source.read(&functions[0]), Some("1.2.0"));
+        assert!(functions[1].env_vars.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fleet_health_covers_functions_without_env_vars() {
+        let mut old = function("movies-front-prod", None);
+        old.runtime = Some(aws_sdk_lambda::types::Runtime::Nodejs16x);
+        let lambda = FakeLambda(vec![old, function("scraper-prod", Some("1.2.0"))]);
+        let filter = LambdaFilter::default();
+        let functions =
+            get_deployed_lambdas_list(&lambda, &filter, None, None, &Shutdown::default())
+                .await
+                .unwrap();
+
+        let rules = health::Rules {
+            min_node_major: 20,
+            stale_days: None,
+        };
+        let today = health::parse_date("2026-10-14").unwrap();
+        let mut out = Vec::new();
+        let flagged = health::write_report(&mut out, &functions, &rules, today).unwrap();
+        assert_eq!(flagged, 1);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("Fleet health (2 functions):\n  movies-front-prod: runtime nodejs16.x"));
+    }
+
+    #[tokio::test]
+    async fn env_filters_must_all_match() {
+        let with_env = |name: &str, vars: &[(&str, &str)]| {
+            let mut environment = EnvironmentResponse::builder();
+            for (key, value) in vars {
+                environment = environment.variables(*key, *value);
+            }
+            let mut function = function(name, None);
+            function.environment = Some(environment.build());
+            function
+        };
+        let lambda = FakeLambda(vec![
+            with_env("scraper-prod", &[("STAGE", "prod"), ("TEAM", "data")]),
+            with_env("scraper-staging", &[("STAGE", "staging"), ("TEAM", "data")]),
+            with_env("movies-front-prod", &[("STAGE", "prod")]),
+        ]);
+        let filter = LambdaFilter {
+            env: vec!["STAGE=prod".parse().unwrap(), "TEAM=data".parse().unwrap()],
+            ..Default::default()
+        };
+
+        let functions =
+            get_deployed_lambdas_list(&lambda, &filter, None, None, &Shutdown::default())
+                .await
+                .unwrap();
+        let names: Vec<_> = functions.iter().map(|fnc| fnc.name.as_str()).collect();
+        assert_eq!(names, ["scraper-prod"]);
+        assert!("=prod".parse::<EnvFilter>().is_err());
+        assert!("STAGE".parse::<EnvFilter>().is_err());
+    }
+
+    #[tokio::test]
+    async fn vpc_filter_keeps_functions_in_that_vpc_only() {
+        let in_vpc = |name: &str, vpc_id: &str| {
+            let mut function = function(name, Some("1.0.0"));
+            function.vpc_config = Some(
+                VpcConfigResponse::builder()
+                    .vpc_id(vpc_id)
+                    .subnet_ids("subnet-1")
+                    .build(),
+            );
+            function
+        };
+        let lambda = FakeLambda(vec![
+            in_vpc("scraper-prod", "vpc-prod"),
+            in_vpc("scraper-staging", "vpc-staging"),
+            // Listed without VPC config, so it's looked up, and isn't in one.
+            function("movies-front-prod", Some("1.0.0")),
+        ]);
+        let filter = LambdaFilter {
+            vpc_id: Some("vpc-prod".to_string()),
+            ..Default::default()
+        };
+
+        let functions =
+            get_deployed_lambdas_list(&lambda, &filter, None, None, &Shutdown::default())
+                .await
+                .unwrap();
+        let names: Vec<_> = functions.iter().map(|fnc| fnc.name.as_str()).collect();
+        assert_eq!(names, ["scraper-prod"]);
+        assert_eq!(functions[0].vpc.as_ref().unwrap().subnet_ids, ["subnet-1"]);
+    }
+
+    #[tokio::test]
+    async fn listings_resume_from_the_checkpointed_marker() {
+        let lambda = FakeLambda(vec![
+            function("scraper-prod", Some("1.2.0")),
+            function("movies-front-prod", Some("2.0.0")),
+            function("standen-node-prod", Some("0.3.1")),
+        ]);
+        let filter = LambdaFilter::default();
+        let path = std::env::temp_dir().join(format!("beacon2-listing-{}", std::process::id()));
+        let checkpoint = Checkpoint::open(&path, false).unwrap();
+        // As left by a run interrupted after the first two pages.
+        let saved = Lambda {
+            name: "scraper-prod".to_string(),
+            ..Default::default()
+        };
+        checkpoint.record_listing(
+            "default@eu-west-1",
+            Listing {
+                next_marker: Some("2".to_string()),
+                total_functions: 2,
+                functions: vec![saved],
+            },
+        );
+        let resumed = Checkpoint::open(&path, true).unwrap();
+
+        let functions = get_deployed_lambdas_list(
+            &lambda,
+            &filter,
+            None,
+            Some((&resumed, "default@eu-west-1")),
+            &Shutdown::default(),
+        )
+        .await
+        .unwrap();
+        let names: Vec<_> = functions.iter().map(|fnc| fnc.name.as_str()).collect();
+        assert_eq!(names, ["scraper-prod", "standen-node-prod"]);
+        let listing = resumed.listing("default@eu-west-1").unwrap();
+        assert_eq!(listing.next_marker, None);
+        assert_eq!(listing.total_functions, 3);
+        resumed.finish();
+    }
+
+    #[tokio::test]
+    async fn listings_are_redacted_before_they_are_checkpointed() {
+        let mut function = function("scraper-prod", Some("1.2.0"));
+        function.environment = Some(
+            EnvironmentResponse::builder()
+                .variables("APP_VERSION", "1.2.0")
+                .variables("DB_PASSWORD", "hunter2")
+                .build(),
+        );
+        let lambda = FakeLambda(vec![function]);
+        let filter = LambdaFilter::default();
+        let redactor = redact::Redactor {
+            allow: Vec::new(),
+            deny: Vec::new(),
+        };
+        let path = std::env::temp_dir().join(format!("beacon2-redacted-{}", std::process::id()));
+        let checkpoint = Checkpoint::open(&path, false).unwrap();
+        let shutdown = Shutdown::default();
+
+        let functions = get_deployed_lambdas_list(
+            &lambda,
+            &filter,
+            Some(&redactor),
+            Some((&checkpoint, "default@eu-west-1")),
+            &shutdown,
+        )
+        .await
+        .unwrap();
+        assert_eq!(functions[0].env_vars["DB_PASSWORD"], redact::REDACTED);
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("1.2.0") && !saved.contains("hunter2"));
+        checkpoint.finish();
+    }
+
+    #[tokio::test]
+    async fn package_size_limit_holds_without_a_content_length() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/package.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n");
+            for _ in 0..20 {
+                let _ = stream.write_all(format!("64\r\n{}\r\n", "x".repeat(100)).as_bytes());
+            }
+            let _ = stream.write_all(b"0\r\n\r\n");
+        });
+
+        let error = read_package_file(&url, "package.json", 1000)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("1000 byte limit"), "{}", error);
+    }
+}
@@ -0,0 +1,527 @@
+//! Decides which deployed function belongs to which repo.
+
+use crate::Lambda;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStrategy {
+    /// The function name contains the repo name
+    Contains,
+    /// The function name is the repo name
+    Exact,
+    /// The function name is the repo name, or it with one of `--stages` as a prefix
+    /// or suffix, e.g. `scraper-prod` or `dev_scraper`
+    Stage,
+    /// The function's `--match-tag` tag names the repo, as `repo` or `owner/repo`
+    Tag,
+    /// The function name is the most similar to the repo name (Jaro-Winkler)
+    Fuzzy,
+}
+
+/// What is compared against function names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKey {
+    /// The repo name
+    Repo,
+    /// package.json's `name`, falling back to the repo name when it has none
+    Name,
+}
+
+/// A repo's own rule from `--config`, used instead of the strategy and template.
+#[derive(Clone, Debug)]
+pub enum MatchRule {
+    /// The function's exact name.
+    Name(String),
+    /// A regex the function name has to match.
+    Pattern(Regex),
+}
+
+pub enum MatchOutcome<'a> {
+    /// `score` is the similarity for fuzzy matches.
+    Matched {
+        lambda: &'a Lambda,
+        score: Option<f64>,
+    },
+    /// Several functions scored equally well, so none was picked.
+    Ambiguous(Vec<&'a Lambda>),
+    NotFound,
+}
+
+/// How many of the most similar names `--explain` shows for fuzzy matching.
+const EXPLAIN_CLOSEST: usize = 3;
+
+pub struct Matcher {
+    pub strategy: MatchStrategy,
+    /// Minimum Jaro-Winkler similarity (0.0..=1.0) for a fuzzy match.
+    pub fuzzy_threshold: f64,
+    /// When set, e.g. `acme-{repo}-prod`, the exact function name to look for,
+    /// overriding `strategy`.
+    pub name_template: Option<String>,
+    pub match_key: MatchKey,
+    /// Drop the `@scope/` from package names used as the match key.
+    pub strip_scope: bool,
+    /// Per-repo rules, keyed by repo name, overriding everything else.
+    pub rules: BTreeMap<String, MatchRule>,
+    /// Stage names `MatchStrategy::Stage` strips, e.g. `prod`.
+    pub stages: Vec<String>,
+    /// The tag `MatchStrategy::Tag` reads.
+    pub tag_key: String,
+}
+
+/// The command line's defaults.
+impl Default for Matcher {
+    fn default() -> Self {
+        Matcher {
+            strategy: MatchStrategy::Contains,
+            fuzzy_threshold: 0.85,
+            name_template: None,
+            match_key: MatchKey::Repo,
+            strip_scope: false,
+            rules: BTreeMap::new(),
+            stages: Vec::new(),
+            tag_key: "repo".to_string(),
+        }
+    }
+}
+
+/// What a function has to satisfy to belong to one repo.
+enum Criterion<'a> {
+    Template(String),
+    Mapped(&'a str),
+    Pattern(&'a Regex),
+    Contains(&'a str),
+    Exact(&'a str),
+    Stage(&'a str),
+    Tag(&'a str),
+    Fuzzy(String),
+}
+
+/// `@acme/payments` becomes `payments`; unscoped names are returned as is.
+fn without_scope(name: &str) -> &str {
+    name.strip_prefix('@')
+        .and_then(|scoped| scoped.split_once('/'))
+        .map_or(name, |(_, name)| name)
+}
+
+impl Matcher {
+    /// The function name `name_template` gives for `repo`. `{name}` is the
+    /// package name without its `@scope/`, or the repo name if there is none.
+    pub fn expected_name(&self, repo: &str, package_name: Option<&str>) -> Option<String> {
+        let template = self.name_template.as_ref()?;
+        let name = package_name.map(without_scope).unwrap_or(repo);
+        Some(template.replace("{repo}", repo).replace("{name}", name))
+    }
+
+    /// Tag matching needs every function's tags, not just the matched ones'.
+    pub fn needs_tags(&self) -> bool {
+        self.strategy == MatchStrategy::Tag
+    }
+
+    /// The name compared against function names under `match_key`.
+    fn key<'a>(&self, repo: &'a str, package_name: Option<&'a str>) -> &'a str {
+        match (self.match_key, package_name) {
+            (MatchKey::Name, Some(name)) if self.strip_scope => without_scope(name),
+            (MatchKey::Name, Some(name)) => name,
+            _ => repo,
+        }
+    }
+
+    fn criterion<'a>(&'a self, repo: &'a str, package_name: Option<&'a str>) -> Criterion<'a> {
+        match self.rules.get(repo) {
+            Some(MatchRule::Name(name)) => return Criterion::Mapped(name),
+            Some(MatchRule::Pattern(pattern)) => return Criterion::Pattern(pattern),
+            None => {}
+        }
+        if let Some(expected) = self.expected_name(repo, package_name) {
+            return Criterion::Template(expected);
+        }
+
+        let key = self.key(repo, package_name);
+        match self.strategy {
+            MatchStrategy::Contains => Criterion::Contains(key),
+            MatchStrategy::Exact => Criterion::Exact(key),
+            MatchStrategy::Stage => Criterion::Stage(key),
+            MatchStrategy::Tag => Criterion::Tag(repo),
+            MatchStrategy::Fuzzy => Criterion::Fuzzy(key.to_lowercase()),
+        }
+    }
+
+    fn accepts(&self, criterion: &Criterion, fnc: &Lambda) -> bool {
+        match criterion {
+            Criterion::Template(name) => fnc.name == *name,
+            Criterion::Mapped(name) => fnc.name == *name,
+            Criterion::Pattern(pattern) => pattern.is_match(&fnc.name),
+            Criterion::Contains(key) => fnc.name.contains(key),
+            Criterion::Exact(key) => fnc.name == *key,
+            Criterion::Stage(key) => self.without_stage(&fnc.name).eq_ignore_ascii_case(key),
+            Criterion::Tag(repo) => fnc.tags.get(&self.tag_key).is_some_and(|tagged| {
+                tagged == repo
+                    || tagged
+                        .rsplit_once('/')
+                        .is_some_and(|(_, name)| name == *repo)
+            }),
+            Criterion::Fuzzy(key) => {
+                strsim::jaro_winkler(key, &fnc.name.to_lowercase()) >= self.fuzzy_threshold
+            }
+        }
+    }
+
+    /// `name` without one of `stages` as a `-` or `_` separated prefix or suffix.
+    fn without_stage<'a>(&self, name: &'a str) -> &'a str {
+        for stage in &self.stages {
+            for separator in ['-', '_'] {
+                let suffix = format!("{}{}", separator, stage);
+                let prefix = format!("{}{}", stage, separator);
+                if let Some(stripped) = name.strip_suffix(&suffix) {
+                    return stripped;
+                }
+                if let Some(stripped) = name.strip_prefix(&prefix) {
+                    return stripped;
+                }
+            }
+        }
+        name
+    }
+
+    pub fn find<'a>(
+        &self,
+        repo: &str,
+        package_name: Option<&str>,
+        deployed_lambdas: &'a [Lambda],
+    ) -> MatchOutcome<'a> {
+        let functions: Vec<&Lambda> = deployed_lambdas.iter().collect();
+        self.find_among(&self.criterion(repo, package_name), &functions)
+    }
+
+    /// What `find` reaches for `repo` in each account and region the functions
+    /// were listed from, so a service deployed to several is compared in each.
+    /// Locations without a match are left out, unless none has one.
+    pub fn find_each_location<'a>(
+        &self,
+        repo: &str,
+        package_name: Option<&str>,
+        deployed_lambdas: &'a [Lambda],
+    ) -> Vec<MatchOutcome<'a>> {
+        type Location<'l> = (Option<&'l str>, Option<&'l str>);
+        let mut locations: Vec<(Location, Vec<&Lambda>)> = Vec::new();
+        for fnc in deployed_lambdas {
+            let location = (fnc.account_id.as_deref(), fnc.region.as_deref());
+            match locations.iter_mut().find(|(seen, _)| *seen == location) {
+                Some((_, functions)) => functions.push(fnc),
+                None => locations.push((location, vec![fnc])),
+            }
+        }
+
+        let criterion = self.criterion(repo, package_name);
+        let outcomes: Vec<MatchOutcome> = locations
+            .iter()
+            .map(|(_, functions)| self.find_among(&criterion, functions))
+            .filter(|outcome| !matches!(outcome, MatchOutcome::NotFound))
+            .collect();
+        if outcomes.is_empty() {
+            vec![MatchOutcome::NotFound]
+        } else {
+            outcomes
+        }
+    }
+
+    fn find_among<'a>(&self, criterion: &Criterion, functions: &[&'a Lambda]) -> MatchOutcome<'a> {
+        match criterion {
+            Criterion::Fuzzy(key) => self.find_fuzzy(key, functions),
+            criterion => functions
+                .iter()
+                .find(|fnc| self.accepts(criterion, fnc))
+                .map_or(MatchOutcome::NotFound, |lambda| MatchOutcome::Matched {
+                    lambda,
+                    score: None,
+                }),
+        }
+    }
+
+    /// Every function the rule accepts for `repo`, not just the one `find` picks,
+    /// e.g. the same service deployed to several regions or accounts.
+    pub fn all_matches<'a>(
+        &self,
+        repo: &str,
+        package_name: Option<&str>,
+        deployed_lambdas: &'a [Lambda],
+    ) -> Vec<&'a Lambda> {
+        let criterion = self.criterion(repo, package_name);
+        deployed_lambdas
+            .iter()
+            .filter(|fnc| self.accepts(&criterion, fnc))
+            .collect()
+    }
+
+    /// Describes, one line per step, how `find` reaches its outcome for `repo`.
+    pub fn explain(
+        &self,
+        repo: &str,
+        package_name: Option<&str>,
+        deployed_lambdas: &[Lambda],
+    ) -> Vec<String> {
+        let criterion = self.criterion(repo, package_name);
+        let mut lines = vec![format!(
+            "considered {} function(s) with the {} rule",
+            deployed_lambdas.len(),
+            Self::rule(&criterion)
+        )];
+
+        match &criterion {
+            Criterion::Fuzzy(key) => {
+                let mut scored: Vec<(f64, &str)> = deployed_lambdas
+                    .iter()
+                    .map(|fnc| {
+                        let score = strsim::jaro_winkler(key, &fnc.name.to_lowercase());
+                        (score, fnc.name.as_str())
+                    })
+                    .collect();
+                scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+                for (score, name) in scored.iter().take(EXPLAIN_CLOSEST) {
+                    let verdict = if *score >= self.fuzzy_threshold {
+                        "meets"
+                    } else {
+                        "below"
+                    };
+                    lines.push(format!(
+                        "{} scored {:.3}, {} the {} threshold",
+                        name, score, verdict, self.fuzzy_threshold
+                    ));
+                }
+            }
+            Criterion::Template(_) | Criterion::Mapped(_) => {}
+            criterion => {
+                let accepted: Vec<&str> = deployed_lambdas
+                    .iter()
+                    .filter(|fnc| self.accepts(criterion, fnc))
+                    .map(|fnc| fnc.name.as_str())
+                    .collect();
+                if accepted.len() > 1 {
+                    let described = match criterion {
+                        Criterion::Contains(key) => format!("names containing `{}`", key),
+                        _ => "accepted names".to_string(),
+                    };
+                    lines.push(format!(
+                        "{}: {}; the first listed in each account and region wins",
+                        described,
+                        accepted.join(", ")
+                    ));
+                }
+            }
+        }
+
+        lines.push(match self.find(repo, package_name, deployed_lambdas) {
+            MatchOutcome::Matched { lambda, .. } => {
+                format!(
+                    "matched {} by the {} rule",
+                    lambda.name,
+                    Self::rule(&criterion)
+                )
+            }
+            MatchOutcome::Ambiguous(candidates) => format!(
+                "ambiguous between {}",
+                candidates
+                    .iter()
+                    .map(|fnc| fnc.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            MatchOutcome::NotFound => match &criterion {
+                Criterion::Template(name) => format!("no function is named {}", name),
+                Criterion::Mapped(name) => format!("no function is named {}", name),
+                Criterion::Pattern(pattern) => {
+                    format!("no function name matches `{}`", pattern)
+                }
+                Criterion::Contains(key) => format!("no function name contains `{}`", key),
+                Criterion::Exact(key) => format!("no function is named {}", key),
+                Criterion::Stage(key) => format!(
+                    "no function is named {}, with or without a stage ({})",
+                    key,
+                    self.stages.join(", ")
+                ),
+                Criterion::Tag(repo) => {
+                    format!("no function has a {} tag naming {}", self.tag_key, repo)
+                }
+                Criterion::Fuzzy(key) => format!(
+                    "no function scored at least {} against `{}`",
+                    self.fuzzy_threshold, key
+                ),
+            },
+        });
+
+        lines
+    }
+
+    fn rule(criterion: &Criterion) -> &'static str {
+        match criterion {
+            Criterion::Template(_) => "name template",
+            Criterion::Mapped(_) => "configured name",
+            Criterion::Pattern(_) => "pattern",
+            Criterion::Contains(_) => "substring",
+            Criterion::Exact(_) => "exact",
+            Criterion::Stage(_) => "stage",
+            Criterion::Tag(_) => "tag",
+            Criterion::Fuzzy(_) => "fuzzy",
+        }
+    }
+
+    fn find_fuzzy<'a>(&self, key: &str, functions: &[&'a Lambda]) -> MatchOutcome<'a> {
+        let key = key.to_lowercase();
+        let scored: Vec<(f64, &Lambda)> = functions
+            .iter()
+            .map(|fnc| (strsim::jaro_winkler(&key, &fnc.name.to_lowercase()), *fnc))
+            .filter(|(score, _)| *score >= self.fuzzy_threshold)
+            .collect();
+
+        let Some(best) = scored.iter().map(|(score, _)| *score).reduce(f64::max) else {
+            return MatchOutcome::NotFound;
+        };
+
+        let mut best_matches: Vec<&Lambda> = scored
+            .iter()
+            .filter(|(score, _)| (best - score).abs() < f64::EPSILON)
+            .map(|(_, fnc)| *fnc)
+            .collect();
+
+        if best_matches.len() > 1 {
+            return MatchOutcome::Ambiguous(best_matches);
+        }
+
+        MatchOutcome::Matched {
+            lambda: best_matches.remove(0),
+            score: Some(best),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lambda(name: &str) -> Lambda {
+        Lambda {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fuzzy_matches_near_names_and_reports_ties() {
+        let matcher = Matcher {
+            strategy: MatchStrategy::Fuzzy,
+            ..Default::default()
+        };
+        let lambdas = vec![
+            lambda("movies-frontend-prod"),
+            lambda("scraper-eu"),
+            lambda("scraper-us"),
+        ];
+
+        assert!(matches!(
+            matcher.find("movies-front", None, &lambdas),
+            MatchOutcome::Matched { lambda, score: Some(_) } if lambda.name == "movies-frontend-prod"
+        ));
+        assert!(matches!(
+            matcher.find("scraper", None, &lambdas),
+            MatchOutcome::Ambiguous(candidates) if candidates.len() == 2
+        ));
+        assert!(matches!(
+            matcher.find("standen-node", None, &lambdas),
+            MatchOutcome::NotFound
+        ));
+    }
+
+    #[test]
+    fn explain_ends_with_the_outcome() {
+        let matcher = Matcher::default();
+        let lambdas = vec![lambda("scraper-eu"), lambda("scraper-us")];
+
+        let lines = matcher.explain("scraper", None, &lambdas);
+        assert_eq!(
+            lines.last().unwrap(),
+            "matched scraper-eu by the substring rule"
+        );
+
+        let lines = matcher.explain("movies-front", None, &lambdas);
+        assert_eq!(
+            lines.last().unwrap(),
+            "no function name contains `movies-front`"
+        );
+    }
+
+    #[test]
+    fn name_template_requires_an_exact_name() {
+        let matcher = Matcher {
+            name_template: Some("acme-{name}-prod".to_string()),
+            ..Default::default()
+        };
+        let lambdas = vec![lambda("acme-scraper-prod"), lambda("acme-scraper-prod-old")];
+
+        assert!(matches!(
+            matcher.find("scraper", Some("@acme/scraper"), &lambdas),
+            MatchOutcome::Matched { lambda, .. } if lambda.name == "acme-scraper-prod"
+        ));
+        assert!(matches!(
+            matcher.find("scrape", None, &lambdas),
+            MatchOutcome::NotFound
+        ));
+    }
+
+    #[test]
+    fn stage_tag_and_configured_rules() {
+        let mut matcher = Matcher {
+            strategy: MatchStrategy::Stage,
+            stages: vec!["prod".to_string()],
+            ..Default::default()
+        };
+        let mut tagged = lambda("svc-7f3a");
+        tagged.tags = HashMap::from([("repo".to_string(), "Mikeysauce/movies-front".to_string())]);
+        let lambdas = vec![lambda("movie-scraper-prod"), lambda("scraper-prod"), tagged];
+
+        assert!(matches!(
+            matcher.find("scraper", None, &lambdas),
+            MatchOutcome::Matched { lambda, .. } if lambda.name == "scraper-prod"
+        ));
+
+        matcher.strategy = MatchStrategy::Tag;
+        assert!(matches!(
+            matcher.find("movies-front", None, &lambdas),
+            MatchOutcome::Matched { lambda, .. } if lambda.name == "svc-7f3a"
+        ));
+
+        matcher.rules.insert(
+            "scraper".to_string(),
+            MatchRule::Pattern(Regex::new("^movie-").unwrap()),
+        );
+        assert!(matches!(
+            matcher.find("scraper", None, &lambdas),
+            MatchOutcome::Matched { lambda, .. } if lambda.name == "movie-scraper-prod"
+        ));
+    }
+
+    #[test]
+    fn strip_scope_lets_scoped_package_names_match() {
+        let mut matcher = Matcher {
+            match_key: MatchKey::Name,
+            ..Default::default()
+        };
+        let lambdas = vec![lambda("payments-prod")];
+
+        assert!(matches!(
+            matcher.find("payments-service", Some("@acme/payments"), &lambdas),
+            MatchOutcome::NotFound
+        ));
+
+        matcher.strip_scope = true;
+        assert!(matches!(
+            matcher.find("payments-service", Some("@acme/payments"), &lambdas),
+            MatchOutcome::Matched { lambda, .. } if lambda.name == "payments-prod"
+        ));
+    }
+}
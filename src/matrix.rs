@@ -0,0 +1,168 @@
+//! A services-by-environments table of deployed versions.
+
+use crate::{
+    compare::{version_string, Comparison, Status},
+    RepoDetails,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    str::FromStr,
+};
+
+/// A named AWS target, written as `NAME=PROFILE:REGION` on the command line.
+/// Either side of the colon may be left empty to use the default chain.
+#[derive(Clone, Debug)]
+pub struct Environment {
+    pub name: String,
+    pub profile: Option<String>,
+    pub region: Option<String>,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, target) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=PROFILE:REGION, got `{}`", s))?;
+        let (profile, region) = target.split_once(':').unwrap_or((target, ""));
+
+        if name.is_empty() {
+            return Err(format!("missing environment name in `{}`", s));
+        }
+
+        let non_empty = |part: &str| (!part.is_empty()).then(|| part.to_string());
+        Ok(Environment {
+            name: name.to_string(),
+            profile: non_empty(profile),
+            region: non_empty(region),
+        })
+    }
+}
+
+/// `-` marks an environment with no matching function, `?` a function that
+/// doesn't expose its version.
+pub fn write_matrix(
+    out: &mut impl Write,
+    details: &BTreeMap<String, RepoDetails>,
+    environments: &[(&Environment, Vec<Comparison>)],
+) -> io::Result<()> {
+    let mut header = vec!["service".to_string(), "package.json".to_string()];
+    header.extend(environments.iter().map(|(env, _)| env.name.clone()));
+
+    let rows: Vec<Vec<String>> = details
+        .iter()
+        .map(|(repo, details)| {
+            let expected = match &details.version {
+                Ok(Some(version)) => version_string(version),
+                Ok(None) => "none".to_string(),
+                Err(_) => "error".to_string(),
+            };
+
+            let mut row = vec![repo.clone(), expected];
+            row.extend(environments.iter().map(|(_, comparisons)| {
+                let Some(comparison) = comparisons.iter().find(|c| c.repo == repo) else {
+                    return "-".to_string();
+                };
+                match (comparison.status, comparison.deployed) {
+                    (_, Some(deployed)) => deployed.to_string(),
+                    (Status::Ambiguous, None) => "ambiguous".to_string(),
+                    (_, None) if comparison.lambda.is_some() => "?".to_string(),
+                    (_, None) => "-".to_string(),
+                }
+            }));
+            row
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[column].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lambda;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    fn details(version: Option<&str>) -> RepoDetails {
+        RepoDetails {
+            version: Ok(version.map(Value::from)),
+            package_name: None,
+            commit: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn marks_missing_and_unversioned_functions() {
+        let details = BTreeMap::from([
+            ("movies-front".to_string(), details(None)),
+            ("scraper".to_string(), details(Some("1.2.0"))),
+        ]);
+        let staging: Environment = "staging=:eu-west-1".parse().unwrap();
+        let prod: Environment = "prod=live:".parse().unwrap();
+        assert_eq!(staging.profile, None);
+        assert_eq!(prod.profile.as_deref(), Some("live"));
+        assert_eq!(prod.region, None);
+        assert!("=live:eu-west-1".parse::<Environment>().is_err());
+
+        let fnc = Lambda::default();
+        let environments = [
+            (
+                &staging,
+                vec![
+                    Comparison {
+                        lambda: Some(&fnc),
+                        ..Comparison::new("movies-front", Status::Unknown)
+                    },
+                    Comparison {
+                        lambda: Some(&fnc),
+                        deployed: Some("1.2.0"),
+                        ..Comparison::new("scraper", Status::UpToDate)
+                    },
+                ],
+            ),
+            (
+                &prod,
+                vec![
+                    Comparison::new("movies-front", Status::NoLambda),
+                    Comparison {
+                        lambda: Some(&fnc),
+                        deployed: Some("1.1.0"),
+                        ..Comparison::new("scraper", Status::Drift)
+                    },
+                ],
+            ),
+        ];
+
+        let mut out = Vec::new();
+        write_matrix(&mut out, &details, &environments).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "service       package.json  staging  prod\n\
+             movies-front  none          ?        -\n\
+             scraper       1.2.0         1.2.0    1.1.0\n"
+        );
+    }
+}
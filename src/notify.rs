@@ -0,0 +1,193 @@
+//! `--slack-webhook` and `--sns-topic-arn`: a summary of the findings, sent once
+//! the report is written. Runs with fewer than `--notify-threshold` findings
+//! send nothing, so a quiet fleet doesn't spam the channel.
+
+use crate::compare::{Comparison, Status};
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+
+/// A webhook that hangs shouldn't hold up the run, or the next `watch` scan.
+const SLACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The statuses that count as findings worth telling someone about, along with
+/// being below `--min-version`.
+const FINDINGS: [Status; 3] = [Status::Drift, Status::NoLambda, Status::Error];
+
+fn is_finding(comparison: &Comparison) -> bool {
+    FINDINGS.contains(&comparison.status) || comparison.below_minimum
+}
+
+pub struct Summary {
+    /// Short enough for an SNS subject, which is capped at 100 characters.
+    pub title: String,
+    pub text: String,
+}
+
+/// `None` when there are fewer than `threshold` findings.
+pub fn summary(comparisons: &[Comparison], threshold: usize) -> Option<Summary> {
+    let findings = comparisons
+        .iter()
+        .filter(|comparison| is_finding(comparison))
+        .count();
+    if findings == 0 || findings < threshold {
+        return None;
+    }
+
+    let title = format!(
+        "beacon2: {} finding{} across {} repos",
+        findings,
+        if findings == 1 { "" } else { "s" },
+        comparisons.len()
+    );
+    let mut text = title.clone();
+    let sections = FINDINGS
+        .iter()
+        .map(|&status| {
+            let repos: Vec<String> = comparisons
+                .iter()
+                .filter(|comparison| comparison.status == status)
+                .map(describe)
+                .collect();
+            (status.heading(), repos)
+        })
+        .chain(std::iter::once((
+            "Below minimum version",
+            comparisons
+                .iter()
+                .filter(|comparison| comparison.below_minimum)
+                .map(describe)
+                .collect(),
+        )));
+    for (heading, repos) in sections {
+        if repos.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n\n{} ({}):", heading, repos.len()));
+        for repo in repos {
+            text.push_str(&format!("\n• {}", repo));
+        }
+    }
+
+    Some(Summary { title, text })
+}
+
+fn describe(comparison: &Comparison) -> String {
+    match comparison.error {
+        _ if comparison.status == Status::Drift || comparison.below_minimum => format!(
+            "{}: expected {}, deployed {}",
+            comparison.repo,
+            comparison.expected.as_deref().unwrap_or("none"),
+            comparison.deployed.unwrap_or("unknown")
+        ),
+        Some(error) => format!("{}: {}", comparison.repo, error),
+        None => comparison.repo.to_string(),
+    }
+}
+
+/// Posts to a Slack incoming webhook.
+pub async fn slack(webhook: &str, summary: &Summary) -> Result<()> {
+    let payload = serde_json::json!({ "text": summary.text });
+    let client = reqwest::Client::builder()
+        .timeout(SLACK_TIMEOUT)
+        .build()
+        .map_err(|e| anyhow!("Failed to post to Slack: {}", e))?;
+    let response = client
+        .post(webhook)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                anyhow!(
+                    "Slack didn't answer within {}s, the summary may not have been posted",
+                    SLACK_TIMEOUT.as_secs()
+                )
+            } else {
+                anyhow!("Failed to post to Slack: {}", e)
+            }
+        })?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to post to Slack: HTTP {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+pub async fn sns(client: &aws_sdk_sns::Client, topic_arn: &str, summary: &Summary) -> Result<()> {
+    client
+        .publish()
+        .topic_arn(topic_arn)
+        .subject(&summary.title)
+        .message(&summary.text)
+        .send()
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Failed to publish to {}: {}",
+                topic_arn,
+                aws_sdk_sns::error::DisplayErrorContext(&e)
+            )
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison<'a>(repo: &'a str, deployed: Option<&'a str>, status: Status) -> Comparison<'a> {
+        Comparison {
+            expected: Some("1.2.0".to_string()),
+            deployed,
+            ..Comparison::new(repo, status)
+        }
+    }
+
+    #[test]
+    fn summarizes_findings_over_the_threshold() {
+        let comparisons = vec![
+            comparison("scraper", Some("1.0.0"), Status::Drift),
+            comparison("movies-front", Some("1.2.0"), Status::UpToDate),
+            comparison("standen-node", None, Status::NoLambda),
+        ];
+
+        assert!(summary(&comparisons, 3).is_none());
+        assert!(summary(&comparisons[1..2], 1).is_none());
+
+        let summary = summary(&comparisons, 2).unwrap();
+        assert_eq!(summary.title, "beacon2: 2 findings across 3 repos");
+        assert_eq!(
+            summary.text,
+            "beacon2: 2 findings across 3 repos\n\n\
+             Drift (1):\n• scraper: expected 1.2.0, deployed 1.0.0\n\n\
+             No Lambda found (1):\n• standen-node"
+        );
+    }
+
+    #[test]
+    fn repos_below_the_minimum_keep_their_status() {
+        let comparisons = vec![
+            Comparison {
+                below_minimum: true,
+                ..comparison("scraper", Some("1.0.0"), Status::Drift)
+            },
+            Comparison {
+                below_minimum: true,
+                ..comparison("movies-front", Some("1.2.0"), Status::UpToDate)
+            },
+        ];
+
+        let summary = summary(&comparisons, 1).unwrap();
+        assert_eq!(
+            summary.text,
+            "beacon2: 2 findings across 2 repos\n\n\
+             Drift (1):\n• scraper: expected 1.2.0, deployed 1.0.0\n\n\
+             Below minimum version (2):\n\
+             • scraper: expected 1.2.0, deployed 1.0.0\n\
+             • movies-front: expected 1.2.0, deployed 1.2.0"
+        );
+    }
+}
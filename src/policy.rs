@@ -0,0 +1,194 @@
+//! `--dependency-policy`: checks the packages each repo depends on against
+//! banned packages and minimum versions.
+
+use crate::{compare::Version, config};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+const SECTIONS: [&str; 2] = ["dependencies", "devDependencies"];
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+    banned: Vec<String>,
+    /// Package name to the lowest version allowed.
+    minimum: BTreeMap<String, String>,
+}
+
+pub struct Policy {
+    banned: Vec<String>,
+    minimum: BTreeMap<String, Version>,
+}
+
+pub enum Violation<'a> {
+    Banned {
+        package: &'a str,
+        declared: &'a str,
+        section: &'static str,
+    },
+    BelowMinimum {
+        package: &'a str,
+        declared: &'a str,
+        section: &'static str,
+        minimum: &'a Version,
+    },
+}
+
+impl fmt::Display for Violation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Banned {
+                package,
+                declared,
+                section,
+            } => write!(f, "{} {} is banned ({})", package, declared, section),
+            Violation::BelowMinimum {
+                package,
+                declared,
+                section,
+                minimum,
+            } => write!(
+                f,
+                "{} {} is below the minimum {} ({})",
+                package, declared, minimum, section
+            ),
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let (file, _): (PolicyFile, _) = config::read_file(path, "dependency policy")?;
+        let minimum = file
+            .minimum
+            .into_iter()
+            .map(|(package, version)| {
+                let version = version.parse().map_err(|e| {
+                    anyhow!(
+                        "Invalid minimum for {} in {}: {}",
+                        package,
+                        path.display(),
+                        e
+                    )
+                })?;
+                Ok((package, version))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Policy {
+            banned: file.banned,
+            minimum,
+        })
+    }
+
+    /// A range is judged by its lowest version, so `^4.17.0` is below a minimum of
+    /// `4.17.21`. Ranges without one, like `*` or a git URL, are left alone.
+    pub fn violations<'a>(
+        &'a self,
+        package_json: &'a HashMap<String, Value>,
+    ) -> Vec<Violation<'a>> {
+        let mut violations = Vec::new();
+        for section in SECTIONS {
+            let Some(Value::Object(deps)) = package_json.get(section) else {
+                continue;
+            };
+            for (package, declared) in deps {
+                let Some(declared) = declared.as_str() else {
+                    continue;
+                };
+                if self.banned.contains(package) {
+                    violations.push(Violation::Banned {
+                        package,
+                        declared,
+                        section,
+                    });
+                }
+                let Some(minimum) = self.minimum.get(package) else {
+                    continue;
+                };
+                if lowest_version(declared).is_some_and(|lowest| lowest < *minimum) {
+                    violations.push(Violation::BelowMinimum {
+                        package,
+                        declared,
+                        section,
+                        minimum,
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// The first version in a range like `^1.2.0`, `>=1.2.0 <2`, or `~1.2`.
+fn lowest_version(range: &str) -> Option<Version> {
+    let first = range.split_whitespace().next()?;
+    first
+        .trim_start_matches(['^', '~', '>', '=', 'v'])
+        .parse()
+        .ok()
+}
+
+/// `results` holds each violating repo with the function it's deployed as, if any.
+pub fn write_policy_report(
+    out: &mut impl Write,
+    results: &[(&str, Option<&str>, Vec<Violation>)],
+) -> io::Result<()> {
+    writeln!(out, "Dependency policy violations:")?;
+    for (repo, function, violations) in results {
+        let deployed = match function {
+            Some(function) => format!("{} -> {}", repo, function),
+            None => format!("{} (not deployed)", repo),
+        };
+        for violation in violations {
+            writeln!(out, "  {}: {}", deployed, violation)?;
+        }
+    }
+
+    let deployed = results.iter().filter(|(_, function, _)| function.is_some());
+    writeln!(
+        out,
+        "{} repo(s) violate the policy, {} of them deployed",
+        results.len(),
+        deployed.count()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn flags_banned_packages_and_ranges_starting_below_the_minimum() {
+        let policy = Policy {
+            banned: vec!["request".to_string()],
+            minimum: BTreeMap::from([("lodash".to_string(), "4.17.21".parse().unwrap())]),
+        };
+        let package_json: HashMap<String, Value> = serde_json::from_value(json!({
+            "dependencies": { "lodash": "^4.17.0", "request": "^2.88.0" },
+            "devDependencies": { "lodash": ">=4.17.21", "jest": "*" },
+        }))
+        .unwrap();
+
+        let violations: Vec<String> = policy
+            .violations(&package_json)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "lodash ^4.17.0 is below the minimum 4.17.21 (dependencies)",
+                "request ^2.88.0 is banned (dependencies)",
+            ]
+        );
+    }
+}
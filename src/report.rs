@@ -0,0 +1,845 @@
+//! Text rendering of the comparison results.
+
+use crate::{
+    compare::{Comparison, Status},
+    matching::Matcher,
+    Lambda, RepoDetails, Vpc,
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    str::FromStr,
+};
+
+/// Settings for the text report.
+pub struct ReportOptions {
+    /// Where the expected version came from, e.g. `package.json@main`.
+    pub expected_label: String,
+    /// Where deployed versions are read from, e.g. `env var APP_VERSION`.
+    pub deployed_label: String,
+    /// Flag matched functions without a dead-letter queue.
+    pub check_dead_letter: bool,
+}
+
+pub fn write_report(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    options: &ReportOptions,
+) -> io::Result<()> {
+    for comparison in comparisons {
+        // Fetch failures were already reported as they happened.
+        if comparison.status == Status::Error {
+            continue;
+        }
+        let Some(version) = &comparison.expected else {
+            writeln!(
+                out,
+                "Repo {} has no version in {}",
+                comparison.repo, options.expected_label
+            )?;
+            continue;
+        };
+
+        if let Some(fnc) = comparison.lambda {
+            writeln!(out, "-------------------------------------")?;
+            writeln!(out, "Function: {}", fnc.name)?;
+            if let Some(score) = comparison.match_score {
+                writeln!(out, "Match score: {:.2}", score)?;
+            }
+            writeln!(out, "ARN: {}", fnc.arn)?;
+            if let Some(account_id) = &fnc.account_id {
+                writeln!(out, "Account: {}", account_id)?;
+            }
+            if let Some(region) = &fnc.region {
+                writeln!(out, "Region: {}", region)?;
+            }
+            // Only worth a line outside the standard partition, e.g. in GovCloud.
+            if let Some(partition) = fnc.partition.as_deref().filter(|p| *p != "aws") {
+                writeln!(out, "Partition: {}", partition)?;
+            }
+            writeln!(out, "Architectures: {}", fnc.architectures.join(", "))?;
+            if let Some(runtime) = &fnc.runtime {
+                writeln!(out, "Runtime: {}", runtime)?;
+            }
+            if let (Some(memory_size), Some(timeout)) = (fnc.memory_size, fnc.timeout) {
+                writeln!(out, "Memory: {} MB, timeout: {}s", memory_size, timeout)?;
+            }
+            if let Some(code_size) = fnc.code_size {
+                writeln!(out, "Code size: {} bytes", code_size)?;
+            }
+            if let Some(last_modified) = &fnc.last_modified {
+                writeln!(out, "Last modified: {}", last_modified)?;
+            }
+            if let Some(Vpc {
+                vpc_id: Some(vpc_id),
+                subnet_ids,
+            }) = &fnc.vpc
+            {
+                writeln!(out, "VPC: {} (subnets: {})", vpc_id, subnet_ids.join(", "))?;
+            }
+            match &fnc.dead_letter_target {
+                Some(target) => writeln!(out, "Dead-letter queue: {}", target)?,
+                None if options.check_dead_letter => {
+                    writeln!(out, "Dead-letter queue: none configured")?
+                }
+                None => {}
+            }
+            match fnc.provisioned_concurrency {
+                Some(0) => writeln!(out, "Provisioned concurrency: none")?,
+                Some(requested) => writeln!(out, "Provisioned concurrency: {}", requested)?,
+                None => {}
+            }
+            let env_vars: BTreeMap<_, _> = fnc.env_vars.iter().collect();
+            writeln!(out, "Environment variables: {:#?}", env_vars)?;
+            writeln!(out, "{}: {}", options.expected_label, version)?;
+            match comparison.deployed {
+                Some(deployed) => writeln!(out, "Deployed version: {}", deployed)?,
+                None => writeln!(
+                    out,
+                    "Deployed version: matched, but no deployed version found in {}",
+                    options.deployed_label
+                )?,
+            }
+            let status = match comparison.status {
+                Status::Unknown => "Unknown",
+                status => status.heading(),
+            };
+            writeln!(out, "Status: {}", status)?;
+            if let Some(commit) = comparison.commit {
+                writeln!(out, "Read from commit: {}", commit)?;
+            }
+            writeln!(out, "-------------------------------------")?;
+        } else if !comparison.candidates.is_empty() {
+            writeln!(
+                out,
+                "Function with name {} is ambiguous, candidates: {}",
+                comparison.repo,
+                comparison.candidates.join(", ")
+            )?;
+        } else if let Some(expected_function) = &comparison.expected_function {
+            writeln!(
+                out,
+                "Function {} (from the name template) not found for repo {}",
+                expected_function, comparison.repo
+            )?;
+        } else {
+            writeln!(out, "Function with name {} not found", comparison.repo)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints one section per status, in triage order, skipping empty sections.
+pub fn write_grouped_report(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    options: &ReportOptions,
+) -> io::Result<()> {
+    for status in Status::ALL {
+        let section: Vec<_> = comparisons
+            .iter()
+            .filter(|comparison| comparison.status == status)
+            .collect();
+        if section.is_empty() {
+            continue;
+        }
+
+        writeln!(out, "== {} ({}) ==", status.heading(), section.len())?;
+        for comparison in section {
+            write_comparison_line(out, comparison, options)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_comparison_line(
+    out: &mut impl Write,
+    comparison: &Comparison,
+    options: &ReportOptions,
+) -> io::Result<()> {
+    let function = comparison.lambda.map(|fnc| fnc.name.as_str());
+    let expected_label = &options.expected_label;
+
+    match (&comparison.expected, function) {
+        (Some(expected), None) if !comparison.candidates.is_empty() => writeln!(
+            out,
+            "  {}: {} {}, candidates {}",
+            comparison.repo,
+            expected_label,
+            expected,
+            comparison.candidates.join(", ")
+        ),
+        (None, _) => match comparison.error {
+            Some(error) => writeln!(out, "  {}: {}", comparison.repo, error),
+            None => writeln!(out, "  {}: no version, nothing to compare", comparison.repo),
+        },
+        (Some(expected), None) => match &comparison.expected_function {
+            Some(expected_function) => writeln!(
+                out,
+                "  {}: {} {}, no function named {}",
+                comparison.repo, expected_label, expected, expected_function
+            ),
+            None => writeln!(
+                out,
+                "  {}: {} {}, no matching function",
+                comparison.repo, expected_label, expected
+            ),
+        },
+        (Some(expected), Some(function)) => {
+            let missing_dead_letter = options.check_dead_letter
+                && comparison
+                    .lambda
+                    .is_some_and(|fnc| fnc.dead_letter_target.is_none());
+            let deployed = match comparison.deployed {
+                Some(deployed) => format!("deployed {}", deployed),
+                None => format!("no deployed version in {}", options.deployed_label),
+            };
+            writeln!(
+                out,
+                "  {} -> {}: {} {}, {}{}",
+                comparison.repo,
+                function,
+                expected_label,
+                expected,
+                deployed,
+                if missing_dead_letter {
+                    " (no dead-letter queue)"
+                } else {
+                    ""
+                }
+            )
+        }
+    }
+}
+
+/// A `--template` line such as `{repo} {function} {status}`, rendered once per
+/// result. Anything outside the placeholders is printed as is.
+#[derive(Clone, Debug)]
+pub struct OutputTemplate(String);
+
+impl OutputTemplate {
+    pub const PLACEHOLDERS: [&'static str; 7] = [
+        "repo",
+        "function",
+        "pkg_version",
+        "deployed_version",
+        "status",
+        "arn",
+        "commit",
+    ];
+
+    pub fn render(&self, comparison: &Comparison) -> String {
+        let fnc = comparison.lambda;
+        let status = serde_json::to_value(comparison.status)
+            .ok()
+            .and_then(|status| status.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let values = [
+            comparison.repo,
+            fnc.map_or("none", |fnc| fnc.name.as_str()),
+            comparison.expected.as_deref().unwrap_or("none"),
+            comparison.deployed.unwrap_or("unknown"),
+            &status,
+            fnc.map_or("none", |fnc| fnc.arn.as_str()),
+            comparison.commit.unwrap_or("none"),
+        ];
+
+        Self::PLACEHOLDERS
+            .iter()
+            .zip(values)
+            .fold(self.0.clone(), |line, (placeholder, value)| {
+                line.replace(&format!("{{{}}}", placeholder), value)
+            })
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    /// Rejects unknown placeholders, so a typo doesn't print literally on every line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let placeholder = regex::Regex::new(r"\{(\w+)\}").unwrap();
+        for captures in placeholder.captures_iter(s) {
+            let name = &captures[1];
+            if !Self::PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "unknown placeholder {{{}}}, expected one of: {}",
+                    name,
+                    Self::PLACEHOLDERS.join(", ")
+                ));
+            }
+        }
+        Ok(OutputTemplate(s.to_string()))
+    }
+}
+
+pub fn write_templated(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    template: &OutputTemplate,
+) -> io::Result<()> {
+    for comparison in comparisons {
+        writeln!(out, "{}", template.render(comparison))?;
+    }
+
+    Ok(())
+}
+
+/// Writes one comparison as a single line of JSON, flushing so consumers see it
+/// immediately. With `stable`, the same results always serialize identically.
+pub fn write_ndjson_line(
+    out: &mut impl Write,
+    comparison: &Comparison,
+    stable: bool,
+) -> io::Result<()> {
+    if stable {
+        serde_json::to_writer(&mut *out, &stabilize(serde_json::to_value(comparison)?))?;
+    } else {
+        serde_json::to_writer(&mut *out, comparison)?;
+    }
+    writeln!(out)?;
+    out.flush()
+}
+
+/// Writes every comparison as one pretty-printed JSON array.
+pub fn write_json(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    stable: bool,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &to_value(comparisons, stable)?)?;
+    writeln!(out)
+}
+
+pub fn write_yaml(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    stable: bool,
+) -> io::Result<()> {
+    serde_yaml::to_writer(&mut *out, &to_value(comparisons, stable)?).map_err(io::Error::other)
+}
+
+fn to_value(comparisons: &[Comparison], stable: bool) -> io::Result<Value> {
+    let value = serde_json::to_value(comparisons)?;
+    Ok(if stable { stabilize(value) } else { value })
+}
+
+/// One row per repo, with columns padded to line up.
+pub fn write_table(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    expected_label: &str,
+) -> io::Result<()> {
+    let header = [
+        "REPO".to_string(),
+        "FUNCTION".to_string(),
+        expected_label.to_uppercase(),
+        "DEPLOYED".to_string(),
+        "STATUS".to_string(),
+    ];
+    let rows: Vec<[String; 5]> = comparisons
+        .iter()
+        .map(|comparison| {
+            [
+                comparison.repo.to_string(),
+                comparison
+                    .lambda
+                    .map_or("-".to_string(), |fnc| fnc.name.clone()),
+                comparison.expected.clone().unwrap_or("-".to_string()),
+                comparison.deployed.unwrap_or("-").to_string(),
+                comparison.status.heading().to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", cells.join("  ").trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// Sorts object keys all the way down, and the lists inside each comparison. Env
+/// vars and tags come from hash maps and AWS returns lists in no set order, so
+/// without this the same deployment can serialize differently from run to run.
+/// The comparisons themselves keep their order, by repo then function.
+fn stabilize(value: Value) -> Value {
+    match value {
+        Value::Array(comparisons) => {
+            Value::Array(comparisons.into_iter().map(sort_nested).collect())
+        }
+        comparison => sort_nested(comparison),
+    }
+}
+
+fn sort_nested(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map
+                .into_iter()
+                .map(|(key, value)| (key, sort_nested(value)))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(values) => {
+            let mut values: Vec<Value> = values.into_iter().map(sort_nested).collect();
+            values.sort_by_cached_key(Value::to_string);
+            Value::Array(values)
+        }
+        other => other,
+    }
+}
+
+/// Lists repos slowest first, so a single slow repo stands out from GitHub being slow overall.
+pub fn write_timings(
+    out: &mut impl Write,
+    details: &BTreeMap<String, RepoDetails>,
+) -> io::Result<()> {
+    let mut timings: Vec<_> = details
+        .iter()
+        .map(|(repo, details)| (repo, details.elapsed))
+        .collect();
+    timings.sort_by_key(|(_, elapsed)| std::cmp::Reverse(*elapsed));
+
+    writeln!(out, "Fetch timings:")?;
+    for (repo, elapsed) in timings {
+        writeln!(out, "  {}: {}ms", repo, elapsed.as_millis())?;
+    }
+
+    Ok(())
+}
+
+/// Goes to stderr, so it doesn't interleave with machine-readable output.
+pub fn write_explanations(
+    out: &mut impl Write,
+    details: &BTreeMap<String, RepoDetails>,
+    deployed_lambdas: &[Lambda],
+    matcher: &Matcher,
+) -> io::Result<()> {
+    for (repo, details) in details {
+        writeln!(out, "Matching {}:", repo)?;
+        let package_name = details.package_name.as_deref();
+        for line in matcher.explain(repo, package_name, deployed_lambdas) {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deployed functions that no repo matched, or was a candidate for.
+pub fn write_orphans(
+    out: &mut impl Write,
+    comparisons: &[Comparison],
+    deployed_lambdas: &[Lambda],
+) -> io::Result<()> {
+    let claimed = |fnc: &Lambda| {
+        comparisons.iter().any(|comparison| {
+            comparison
+                .lambda
+                .is_some_and(|matched| matched.arn == fnc.arn)
+                || comparison.candidates.contains(&fnc.name.as_str())
+        })
+    };
+    let orphans: Vec<&Lambda> = deployed_lambdas
+        .iter()
+        .filter(|fnc| !claimed(fnc))
+        .collect();
+
+    writeln!(out, "Functions matching no repo ({}):", orphans.len())?;
+    for fnc in orphans {
+        writeln!(out, "  {} ({})", fnc.name, fnc.arn)?;
+    }
+
+    Ok(())
+}
+
+/// Env var totals across the matched functions, split into keys every function
+/// sets and keys only some do, to spot config worth standardizing.
+pub fn write_env_audit(out: &mut impl Write, comparisons: &[Comparison]) -> io::Result<()> {
+    let mut functions: Vec<&Lambda> = comparisons.iter().filter_map(|c| c.lambda).collect();
+    functions.sort_by_key(|fnc| &fnc.arn);
+    functions.dedup_by_key(|fnc| &fnc.arn);
+
+    let mut key_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for fnc in &functions {
+        for key in fnc.env_vars.keys() {
+            *key_counts.entry(key).or_default() += 1;
+        }
+    }
+    let total: usize = key_counts.values().sum();
+
+    writeln!(
+        out,
+        "Env var audit: {} variable(s) across {} matched function(s), {} unique key(s)",
+        total,
+        functions.len(),
+        key_counts.len()
+    )?;
+
+    let (everywhere, partial): (Vec<_>, Vec<_>) = key_counts
+        .iter()
+        .partition(|(_, count)| **count == functions.len());
+
+    let everywhere: Vec<&str> = everywhere.into_iter().map(|(key, _)| *key).collect();
+    writeln!(out, "  In every function: {}", everywhere.join(", "))?;
+    if !partial.is_empty() {
+        writeln!(out, "  In some functions:")?;
+        for (key, count) in partial {
+            writeln!(out, "    {} ({}/{})", key, count, functions.len())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// For each repo matching several functions, the env var keys whose value differs
+/// between them or that only some of them set.
+pub fn write_env_comparison(
+    out: &mut impl Write,
+    groups: &[(&str, Vec<&Lambda>)],
+) -> io::Result<()> {
+    let groups: Vec<_> = groups
+        .iter()
+        .filter(|(_, functions)| functions.len() > 1)
+        .collect();
+    writeln!(
+        out,
+        "Env var differences across {} repo(s) matching several functions:",
+        groups.len()
+    )?;
+
+    for (repo, functions) in groups {
+        // Replicas often share a name, so they're told apart by ARN too.
+        let label = |fnc: &Lambda| match functions.iter().filter(|f| f.name == fnc.name).count() {
+            1 => fnc.name.clone(),
+            _ => fnc.arn.clone(),
+        };
+        let keys: std::collections::BTreeSet<&String> = functions
+            .iter()
+            .flat_map(|fnc| fnc.env_vars.keys())
+            .collect();
+
+        let mut differences = Vec::new();
+        for key in keys {
+            let values: Vec<Option<&String>> =
+                functions.iter().map(|fnc| fnc.env_vars.get(key)).collect();
+            if values.iter().all(|value| *value == values[0]) {
+                continue;
+            }
+            let shown: Vec<String> = functions
+                .iter()
+                .zip(&values)
+                .map(|(fnc, value)| match value {
+                    Some(value) => format!("{}={}", label(fnc), value),
+                    None => format!("{} unset", label(fnc)),
+                })
+                .collect();
+            differences.push(format!("{}: {}", key, shown.join(", ")));
+        }
+
+        writeln!(out, "  {} ({} functions):", repo, functions.len())?;
+        if differences.is_empty() {
+            writeln!(out, "    no differences")?;
+        }
+        for difference in differences {
+            writeln!(out, "    {}", difference)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::{self, DeployedVersion, VersionSource};
+    use std::{collections::HashMap, path::Path};
+
+    fn fixture_dir() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    #[test]
+    fn report_matches_golden_output() {
+        let details =
+            crate::fixtures::load_packagejson_details(&fixture_dir().join("package-json"))
+                .expect("fixtures should load");
+
+        let deployed_lambdas = vec![
+            Lambda {
+                name: "scraper-prod".to_string(),
+                env_vars: HashMap::from([("APP_VERSION".to_string(), "1.2.0".to_string())]),
+                arn: "arn:aws:lambda:eu-west-1:123456789012:function:scraper-prod".to_string(),
+                architectures: vec!["arm64".to_string()],
+                ..Default::default()
+            },
+            Lambda {
+                name: "movies-front-prod".to_string(),
+                env_vars: HashMap::from([("APP_VERSION".to_string(), "0.9.1".to_string())]),
+                arn: "arn:aws:lambda:eu-west-1:123456789012:function:movies-front-prod".to_string(),
+                architectures: vec!["x86_64".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        let matcher = Matcher::default();
+        let deployed_version =
+            DeployedVersion::new(VersionSource::Env("APP_VERSION".to_string()), None);
+        let comparisons =
+            compare::compare(&details, &deployed_lambdas, &deployed_version, &matcher);
+
+        let options = ReportOptions {
+            expected_label: "package.json".to_string(),
+            deployed_label: "env var APP_VERSION".to_string(),
+            check_dead_letter: false,
+        };
+
+        let mut out = Vec::new();
+        write_report(&mut out, &comparisons, &options).unwrap();
+
+        let expected = std::fs::read_to_string(fixture_dir().join("golden/report.txt")).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn table_columns_line_up() {
+        let fnc = Lambda {
+            name: "scraper-prod".to_string(),
+            ..Default::default()
+        };
+        let comparison = |repo, lambda, expected: Option<&str>, deployed, status| Comparison {
+            lambda,
+            expected: expected.map(str::to_string),
+            deployed,
+            ..Comparison::new(repo, status)
+        };
+        let comparisons = vec![
+            comparison(
+                "scraper",
+                Some(&fnc),
+                Some("1.2.0"),
+                Some("1.1.0"),
+                Status::Drift,
+            ),
+            comparison("movies-front", None, Some("2.0.0"), None, Status::NoLambda),
+        ];
+
+        let mut out = Vec::new();
+        write_table(&mut out, &comparisons, "package.json").unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "REPO          FUNCTION      PACKAGE.JSON  DEPLOYED  STATUS\n\
+             scraper       scraper-prod  1.2.0         1.1.0     Drift\n\
+             movies-front  -             2.0.0         -         No Lambda found\n"
+        );
+    }
+
+    #[test]
+    fn orphans_leave_out_matched_functions_and_candidates() {
+        let lambda = |name: &str| Lambda {
+            name: name.to_string(),
+            arn: format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name),
+            ..Default::default()
+        };
+        let deployed_lambdas = vec![
+            lambda("scraper-prod"),
+            lambda("movies-front-blue"),
+            lambda("movies-front-green"),
+            lambda("legacy-cron"),
+        ];
+        let comparisons = vec![
+            Comparison {
+                lambda: Some(&deployed_lambdas[0]),
+                ..Comparison::new("scraper", Status::UpToDate)
+            },
+            Comparison {
+                candidates: vec!["movies-front-blue", "movies-front-green"],
+                ..Comparison::new("movies-front", Status::Ambiguous)
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_orphans(&mut out, &comparisons, &deployed_lambdas).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Functions matching no repo (1):\n\
+             \x20 legacy-cron (arn:aws:lambda:eu-west-1:123456789012:function:legacy-cron)\n"
+        );
+    }
+
+    #[test]
+    fn matched_function_without_a_version_says_where_it_looked() {
+        let fnc = Lambda {
+            name: "scraper-prod".to_string(),
+            ..Default::default()
+        };
+        let comparison = Comparison {
+            lambda: Some(&fnc),
+            expected: Some("1.2.0".to_string()),
+            ..Comparison::new("scraper", Status::Unknown)
+        };
+        let options = ReportOptions {
+            expected_label: "package.json".to_string(),
+            deployed_label: "env var APP_VERSION".to_string(),
+            check_dead_letter: false,
+        };
+
+        let mut out = Vec::new();
+        write_grouped_report(&mut out, &[comparison], &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "== Unknown deployed version (1) ==\n\
+             \x20 scraper -> scraper-prod: package.json 1.2.0, no deployed version in env var APP_VERSION\n\n"
+        );
+    }
+
+    #[test]
+    fn template_fills_placeholders_and_rejects_unknown_ones() {
+        let fnc = Lambda {
+            name: "scraper-prod".to_string(),
+            arn: "arn:aws:lambda:eu-west-1:123456789012:function:scraper-prod".to_string(),
+            ..Default::default()
+        };
+        let comparison = Comparison {
+            lambda: Some(&fnc),
+            expected: Some("1.2.0".to_string()),
+            ..Comparison::new("scraper", Status::Unknown)
+        };
+
+        let template: OutputTemplate =
+            "{repo}\t{function} {pkg_version}/{deployed_version} {status}"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            template.render(&comparison),
+            "scraper\tscraper-prod 1.2.0/unknown unknown"
+        );
+
+        assert!("{repo} {version}".parse::<OutputTemplate>().is_err());
+    }
+
+    #[test]
+    fn env_comparison_shows_differing_and_unset_keys() {
+        let lambda = |name: &str, env: &[(&str, &str)]| Lambda {
+            name: name.to_string(),
+            arn: format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name),
+            env_vars: env
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let eu = lambda("scraper-eu", &[("LOG_LEVEL", "debug"), ("REGION_TAG", "x")]);
+        let us = lambda("scraper-us", &[("LOG_LEVEL", "info"), ("REGION_TAG", "x")]);
+        let ap = lambda("scraper-ap", &[("REGION_TAG", "x")]);
+        let solo = lambda("movies-front", &[("LOG_LEVEL", "info")]);
+
+        let groups = vec![
+            ("scraper", vec![&eu, &us, &ap]),
+            ("movies-front", vec![&solo]),
+        ];
+        let mut out = Vec::new();
+        write_env_comparison(&mut out, &groups).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Env var differences across 1 repo(s) matching several functions:\n\
+             \x20 scraper (3 functions):\n\
+             \x20   LOG_LEVEL: scraper-eu=debug, scraper-us=info, scraper-ap unset\n"
+        );
+    }
+
+    #[test]
+    fn stable_output_sorts_keys_and_lists() {
+        let value = serde_json::json!({
+            "repo": "scraper",
+            "function": {"architectures": ["x86_64", "arm64"], "env_vars": {"B": "2", "A": "1"}},
+        });
+
+        assert_eq!(
+            stabilize(value).to_string(),
+            r#"{"function":{"architectures":["arm64","x86_64"],"env_vars":{"A":"1","B":"2"}},"repo":"scraper"}"#
+        );
+    }
+
+    #[test]
+    fn stable_output_keeps_comparisons_in_repo_order() {
+        let comparisons = [
+            Comparison {
+                commit: Some("ffff"),
+                ..Comparison::new("movies-front", Status::NoLambda)
+            },
+            Comparison {
+                commit: Some("0000"),
+                candidates: vec!["scraper-green", "scraper-blue"],
+                ..Comparison::new("scraper", Status::Ambiguous)
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_json(&mut out, &comparisons, true).unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["repo"], "movies-front");
+        assert_eq!(value[1]["repo"], "scraper");
+        assert_eq!(
+            value[1]["candidates"],
+            serde_json::json!(["scraper-blue", "scraper-green"])
+        );
+    }
+
+    #[test]
+    fn env_audit_splits_shared_and_partial_keys() {
+        let lambda = |name: &str, keys: &[&str]| Lambda {
+            name: name.to_string(),
+            arn: format!("arn:aws:lambda:eu-west-1:123456789012:function:{}", name),
+            env_vars: keys
+                .iter()
+                .map(|key| (key.to_string(), String::new()))
+                .collect(),
+            ..Default::default()
+        };
+        let lambdas = [
+            lambda("scraper", &["APP_VERSION", "LOG_LEVEL"]),
+            lambda("movies-front", &["APP_VERSION", "API_URL"]),
+        ];
+        let comparisons: Vec<Comparison> = lambdas
+            .iter()
+            .map(|fnc| Comparison {
+                lambda: Some(fnc),
+                ..Comparison::new(&fnc.name, Status::Unknown)
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        write_env_audit(&mut out, &comparisons).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Env var audit: 4 variable(s) across 2 matched function(s), 3 unique key(s)\n\
+             \x20 In every function: APP_VERSION\n\
+             \x20 In some functions:\n\
+             \x20   API_URL (1/2)\n\
+             \x20   LOG_LEVEL (1/2)\n"
+        );
+    }
+}
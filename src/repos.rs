@@ -0,0 +1,231 @@
+//! The list of GitHub repos to read expected versions from.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::{fmt, str::FromStr};
+
+/// Owner assumed for repos given without one.
+pub const DEFAULT_OWNER: &str = "Mikeysauce";
+
+const DEFAULT_REPOSITORIES: [&str; 5] = [
+    "Scotski",
+    "scraper",
+    "standen-node",
+    "now-github-starter",
+    "movies-front",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Repo {
+    pub owner: String,
+    pub name: String,
+    /// Branch, tag, or SHA to read this repo at, from `--config`; `--ref` on the
+    /// command line still takes precedence.
+    pub git_ref: Option<String>,
+    /// For a package in a monorepo, the directory its package.json is in, e.g.
+    /// `packages/api`. `None` reads the repo's root.
+    pub package: Option<String>,
+}
+
+impl fmt::Display for Repo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)?;
+        match &self.package {
+            Some(package) => write!(f, "/{}", package),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Repo {
+    type Err = anyhow::Error;
+
+    /// Accepts `owner/repo`, or a bare `repo` under [`DEFAULT_OWNER`].
+    fn from_str(s: &str) -> Result<Self> {
+        Repo::parse(s, DEFAULT_OWNER)
+    }
+}
+
+impl Repo {
+    /// Accepts `owner/repo`, or a bare `repo` under `default_owner`.
+    pub fn parse(s: &str, default_owner: &str) -> Result<Self> {
+        let (owner, name) = s.split_once('/').unwrap_or((default_owner, s));
+        if owner.is_empty() || name.is_empty() || name.contains('/') {
+            return Err(anyhow!("Invalid repo `{}`, expected owner/repo", s));
+        }
+
+        Ok(Repo {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            git_ref: None,
+            package: None,
+        })
+    }
+
+    /// What results are reported and functions matched under: a monorepo
+    /// package's directory name, so each can have its own function, otherwise
+    /// the repo's name.
+    pub fn key(&self) -> &str {
+        self.package
+            .as_deref()
+            .and_then(|package| package.rsplit('/').next())
+            .unwrap_or(&self.name)
+    }
+
+    /// Whether `--only-repos` or `--skip-repos` naming `name` means this repo:
+    /// by name, by key, or in full.
+    pub fn is_named(&self, name: &str) -> bool {
+        name == self.name || name == self.key() || name == self.to_string()
+    }
+
+    /// `path` within the package's directory, or the repo's root.
+    pub fn file_path(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        match &self.package {
+            Some(package) => format!("{}/{}", package.trim_end_matches('/'), path),
+            None => path.to_string(),
+        }
+    }
+
+    /// This repo once per package directory.
+    pub fn with_packages(&self, packages: &[String]) -> Vec<Repo> {
+        packages
+            .iter()
+            .map(|package| Repo {
+                package: Some(package.trim_matches('/').to_string()),
+                ..self.clone()
+            })
+            .collect()
+    }
+}
+
+/// The directories of the package.json files among a repo tree's `paths`,
+/// leaving out the root's, which in a monorepo is the workspace rather than a
+/// service, and any under `node_modules`.
+pub fn packages_in_tree<'a>(paths: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter_map(|path| path.strip_suffix("/package.json"))
+        .filter(|dir| !dir.split('/').any(|part| part == "node_modules"))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn default_repos(owner: &str) -> Vec<Repo> {
+    DEFAULT_REPOSITORIES
+        .iter()
+        .map(|name| Repo {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            git_ref: None,
+            package: None,
+        })
+        .collect()
+}
+
+/// Keeps repos named in `only` (all when empty) and drops those named in `skip`.
+/// Names may be given as `repo` or `owner/repo`.
+pub fn filter_repos(repos: Vec<Repo>, only: &[String], skip: &[String]) -> Vec<Repo> {
+    let named = |repo: &Repo, names: &[String]| names.iter().any(|name| repo.is_named(name));
+
+    repos
+        .into_iter()
+        .filter(|repo| only.is_empty() || named(repo, only))
+        .filter(|repo| !named(repo, skip))
+        .collect()
+}
+
+/// Which of an org's repos `--org` scans.
+pub struct OrgFilter<'a> {
+    /// Any one of these is enough; empty keeps every repo.
+    pub topics: &'a [String],
+    pub language: Option<&'a str>,
+    pub name_pattern: Option<&'a Regex>,
+}
+
+impl OrgFilter<'_> {
+    pub fn keeps(&self, name: &str, topics: &[String], language: Option<&str>) -> bool {
+        let topic_matches = self.topics.is_empty()
+            || self.topics.iter().any(|wanted| {
+                topics
+                    .iter()
+                    .any(|topic| topic.eq_ignore_ascii_case(wanted))
+            });
+        let language_matches = self.language.is_none_or(|wanted| {
+            language.is_some_and(|language| language.eq_ignore_ascii_case(wanted))
+        });
+        let name_matches = self
+            .name_pattern
+            .is_none_or(|pattern| pattern.is_match(name));
+
+        topic_matches && language_matches && name_matches
+    }
+}
+
+/// Parses one repo per line, ignoring blank lines and `#` comments. Bare names
+/// are taken to be under `default_owner`.
+pub fn parse_repo_list(input: &str, default_owner: &str) -> Result<Vec<Repo>> {
+    input
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| Repo::parse(line, default_owner))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repo_list_skips_blanks_and_comments() {
+        let input = "# services\nacme/payments\n\n  scraper  # legacy owner\n#acme/old\n";
+
+        let repos: Vec<String> = parse_repo_list(input, DEFAULT_OWNER)
+            .unwrap()
+            .iter()
+            .map(Repo::to_string)
+            .collect();
+
+        assert_eq!(repos, vec!["acme/payments", "Mikeysauce/scraper"]);
+    }
+
+    #[test]
+    fn monorepo_packages_are_found_and_named_by_directory() {
+        let tree = [
+            "package.json",
+            "packages/api/package.json",
+            "packages/api/src/handler.ts",
+            "packages/worker/package.json",
+            "packages/worker/node_modules/left-pad/package.json",
+        ];
+        let packages = packages_in_tree(tree);
+        assert_eq!(packages, vec!["packages/api", "packages/worker"]);
+
+        let repo = Repo::parse("acme/platform", DEFAULT_OWNER).unwrap();
+        let api = &repo.with_packages(&packages)[0];
+        assert_eq!(api.to_string(), "acme/platform/packages/api");
+        assert_eq!(api.key(), "api");
+        assert!(api.is_named("api") && api.is_named("acme/platform/packages/api"));
+        assert_eq!(api.file_path("package.json"), "packages/api/package.json");
+        assert_eq!(repo.key(), "platform");
+        assert_eq!(repo.file_path("/VERSION"), "VERSION");
+    }
+
+    #[test]
+    fn org_filter_needs_a_topic_the_language_and_the_name() {
+        let topics = vec!["lambda".to_string(), "service".to_string()];
+        let pattern = Regex::new("-api$").unwrap();
+        let filter = OrgFilter {
+            topics: &topics,
+            language: Some("typescript"),
+            name_pattern: Some(&pattern),
+        };
+        let tagged = vec!["Service".to_string()];
+
+        assert!(filter.keeps("payments-api", &tagged, Some("TypeScript")));
+        assert!(!filter.keeps("payments-api", &[], Some("TypeScript")));
+        assert!(!filter.keeps("payments-api", &tagged, None));
+        assert!(!filter.keeps("payments-web", &tagged, Some("TypeScript")));
+    }
+}
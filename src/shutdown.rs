@@ -0,0 +1,36 @@
+//! Ctrl-C handling so an interrupted scan still reports what it collected.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Exit code used when the run was cut short by Ctrl-C (128 + SIGINT).
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    /// Starts listening for Ctrl-C in the background.
+    pub fn install() -> Self {
+        let shutdown = Shutdown::default();
+        let requested = shutdown.requested.clone();
+
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("Interrupted, finishing with partial results...");
+                requested.store(true, Ordering::SeqCst);
+            }
+        });
+
+        shutdown
+    }
+
+    /// Whether Ctrl-C was pressed. Loops check this before issuing another request.
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,24 @@
+//! The SSM requests deployed versions are read with, behind [`SsmApi`] so tests
+//! can answer them without AWS credentials.
+
+use crate::aws_error;
+use aws_sdk_ssm::{error::ProvideErrorMetadata, Client};
+use std::future::Future;
+
+pub trait SsmApi: Sync {
+    /// The parameter's value, or `None` when there's no such parameter.
+    fn get_parameter(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<String>, anyhow::Error>> + Send;
+}
+
+impl SsmApi for Client {
+    async fn get_parameter(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+        match Client::get_parameter(self).name(name).send().await {
+            Ok(resp) => Ok(resp.parameter.and_then(|parameter| parameter.value)),
+            Err(e) if e.code() == Some("ParameterNotFound") => Ok(None),
+            Err(e) => Err(aws_error(e)),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! `--summary` and `--summary-json`: every warning and error of the run in one
+//! place at the end, so CI logs don't have to be searched for them.
+
+use crate::compare::{Comparison, Status};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// Every warning printed so far, in order.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Set from the command line before anything is scanned.
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+#[derive(Default)]
+pub struct Output {
+    /// Print the summary to stderr.
+    pub text: bool,
+    /// Write the summary as JSON to this file.
+    pub json: Option<PathBuf>,
+}
+
+pub fn configure(output: Output) {
+    let _ = OUTPUT.set(output);
+}
+
+/// Prints a warning and keeps it for the summary.
+pub fn warn(message: String) {
+    eprintln!("Warning: {}", message);
+    WARNINGS.lock().unwrap().push(message);
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub exit_code: i32,
+    pub counts: BTreeMap<Status, usize>,
+    pub errors: Vec<Problem>,
+    pub warnings: Vec<String>,
+}
+
+/// `repo` is `None` for a failure of the run as a whole.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub repo: Option<String>,
+    pub message: String,
+}
+
+/// `fatal` is what stopped the run, if anything did.
+pub fn build(
+    exit_code: i32,
+    comparisons: &[Comparison],
+    fatal: Option<&anyhow::Error>,
+    warnings: Vec<String>,
+) -> Summary {
+    let mut counts = BTreeMap::new();
+    for comparison in comparisons {
+        *counts.entry(comparison.status).or_default() += 1;
+    }
+
+    let mut errors: Vec<Problem> = comparisons
+        .iter()
+        .filter_map(|comparison| {
+            comparison.error.map(|error| Problem {
+                repo: Some(comparison.repo.to_string()),
+                message: error.to_string(),
+            })
+        })
+        .collect();
+    if let Some(e) = fatal {
+        errors.push(Problem {
+            repo: None,
+            message: format!("{:#}", e),
+        });
+    }
+
+    Summary {
+        exit_code,
+        counts,
+        errors,
+        warnings,
+    }
+}
+
+pub fn write_text(out: &mut impl Write, summary: &Summary) -> io::Result<()> {
+    writeln!(out, "Summary (exit code {}):", summary.exit_code)?;
+    for (status, count) in &summary.counts {
+        writeln!(out, "  {}: {}", status.heading(), count)?;
+    }
+
+    writeln!(out, "Errors ({}):", summary.errors.len())?;
+    for error in &summary.errors {
+        match &error.repo {
+            Some(repo) => writeln!(out, "  {}: {}", repo, error.message)?,
+            None => writeln!(out, "  {}", error.message)?,
+        }
+    }
+
+    writeln!(out, "Warnings ({}):", summary.warnings.len())?;
+    for warning in &summary.warnings {
+        writeln!(out, "  {}", warning)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the summary wherever the command line asked for it, if anywhere.
+pub fn finish(exit_code: i32, comparisons: &[Comparison], fatal: Option<&anyhow::Error>) {
+    let Some(output) = OUTPUT.get() else {
+        return;
+    };
+    if !output.text && output.json.is_none() {
+        return;
+    }
+
+    let warnings = WARNINGS.lock().unwrap().clone();
+    let summary = build(exit_code, comparisons, fatal, warnings);
+
+    if output.text {
+        if let Err(e) = write_text(&mut io::stderr().lock(), &summary) {
+            eprintln!("Failed to print the summary: {}", e);
+        }
+    }
+    if let Some(path) = &output.json {
+        let written = serde_json::to_vec_pretty(&summary)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(path, content).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("Failed to write the summary to {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FetchError;
+    use anyhow::anyhow;
+
+    fn comparison<'a>(
+        repo: &'a str,
+        status: Status,
+        error: Option<&'a FetchError>,
+    ) -> Comparison<'a> {
+        Comparison {
+            error,
+            ..Comparison::new(repo, status)
+        }
+    }
+
+    #[test]
+    fn lists_counts_errors_and_warnings() {
+        let no_access = FetchError::NoAccess {
+            owner: "Mikeysauce".to_string(),
+            repo: "movies-front".to_string(),
+        };
+        let comparisons = vec![
+            comparison("scraper", Status::Drift, None),
+            comparison("movies-front", Status::Error, Some(&no_access)),
+            comparison("standen-node", Status::Drift, None),
+        ];
+        let fatal = anyhow!("listing Lambda functions failed");
+        let warnings = vec!["hook `notify` exited with 1".to_string()];
+        let summary = build(4, &comparisons, Some(&fatal), warnings);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &summary).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Summary (exit code 4):\n\
+             \x20 Drift: 2\n\
+             \x20 Errors: 1\n\
+             Errors (2):\n\
+             \x20 movies-front: token cannot access Mikeysauce/movies-front\n\
+             \x20 listing Lambda functions failed\n\
+             Warnings (1):\n\
+             \x20 hook `notify` exited with 1\n"
+        );
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["counts"]["drift"], 2);
+        assert_eq!(json["errors"][0]["repo"], "movies-front");
+        assert_eq!(json["errors"][1]["repo"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,159 @@
+//! `--tui`: browse the results interactively instead of printing a report.
+
+use crate::compare::{Comparison, Status};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+use std::io;
+
+const HELP: &str = " ↑/↓ select · f filter by status · q quit ";
+
+pub fn run(comparisons: &[Comparison], expected_label: &str) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = Browser::new(comparisons, expected_label).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Browser<'a, 'c> {
+    comparisons: &'a [Comparison<'c>],
+    expected_label: &'a str,
+    /// `None` shows every status.
+    filter: Option<Status>,
+    selected: ListState,
+}
+
+impl<'a, 'c> Browser<'a, 'c> {
+    fn new(comparisons: &'a [Comparison<'c>], expected_label: &'a str) -> Self {
+        Browser {
+            comparisons,
+            expected_label,
+            filter: None,
+            selected: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.selected.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.selected.select_previous(),
+                KeyCode::Char('f') | KeyCode::Tab => self.next_filter(),
+                _ => {}
+            }
+        }
+    }
+
+    fn visible(&self) -> Vec<&'a Comparison<'c>> {
+        self.comparisons
+            .iter()
+            .filter(|comparison| self.filter.is_none_or(|status| comparison.status == status))
+            .collect()
+    }
+
+    /// Cycles through all statuses, then back to showing everything.
+    fn next_filter(&mut self) {
+        self.filter = match self.filter {
+            None => Some(Status::ALL[0]),
+            Some(status) => Status::ALL
+                .iter()
+                .skip_while(|s| **s != status)
+                .nth(1)
+                .copied(),
+        };
+        self.selected.select(Some(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let visible = self.visible();
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(frame.area());
+
+        let title = match self.filter {
+            Some(status) => format!(" {} ({}) ", status.heading(), visible.len()),
+            None => format!(" All ({}) ", visible.len()),
+        };
+        let items: Vec<ListItem> = visible
+            .iter()
+            .map(|comparison| {
+                ListItem::new(format!(
+                    "{} [{}]",
+                    comparison.repo,
+                    comparison.status.heading()
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(title).title_bottom(HELP))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.selected);
+
+        let detail = self
+            .selected
+            .selected()
+            .and_then(|index| visible.get(index))
+            .map(|comparison| self.detail_lines(comparison))
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(detail)
+                .block(Block::bordered().title(" Details "))
+                .wrap(Wrap { trim: false }),
+            detail_area,
+        );
+    }
+
+    fn detail_lines(&self, comparison: &Comparison) -> Vec<Line<'static>> {
+        let mut lines = vec![
+            Line::from(format!("Repo: {}", comparison.repo)),
+            Line::from(format!("Status: {}", comparison.status.heading())),
+            Line::from(format!(
+                "{}: {}",
+                self.expected_label,
+                comparison.expected.as_deref().unwrap_or("none")
+            )),
+            Line::from(format!(
+                "Deployed: {}",
+                comparison.deployed.unwrap_or("unknown")
+            )),
+        ];
+        if let Some(error) = comparison.error {
+            lines.push(Line::from(format!("Error: {}", error)));
+        }
+        if !comparison.candidates.is_empty() {
+            lines.push(Line::from(format!(
+                "Candidates: {}",
+                comparison.candidates.join(", ")
+            )));
+        }
+
+        if let Some(fnc) = comparison.lambda {
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!("Function: {}", fnc.name)));
+            lines.push(Line::from(format!("ARN: {}", fnc.arn)));
+            lines.push(Line::from("Environment variables:"));
+
+            let mut env_vars: Vec<_> = fnc.env_vars.iter().collect();
+            env_vars.sort();
+            for (key, value) in env_vars {
+                lines.push(Line::from(format!("  {}={}", key, value)));
+            }
+        }
+
+        lines
+    }
+}
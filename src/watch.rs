@@ -0,0 +1,97 @@
+//! The `watch` subcommand's pieces: how often to scan, and what counts as the
+//! results having changed between scans.
+
+use crate::{compare::Comparison, shutdown::Shutdown};
+use std::time::Duration;
+
+/// A number of seconds, or of minutes, hours, or days with an `m`, `h`, or `d`
+/// suffix, e.g. `15m`.
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit `{}`, use s, m, h, or d", unit)),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("`{}` is not a number of seconds, or e.g. `15m`", s))?;
+    match number.checked_mul(seconds_per_unit) {
+        Some(0) => Err("the interval must be longer than zero".to_string()),
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => Err(format!("`{}` is too large an interval", s)),
+    }
+}
+
+/// What a scan found, reduced to what a change worth reporting would touch.
+pub fn fingerprint(comparisons: &[Comparison]) -> Vec<String> {
+    let mut state: Vec<String> = comparisons
+        .iter()
+        .map(|comparison| {
+            serde_json::json!([
+                comparison.repo,
+                comparison.lambda.map(|fnc| &fnc.arn),
+                comparison.expected,
+                comparison.deployed,
+                comparison.status,
+                comparison.error.map(ToString::to_string),
+            ])
+            .to_string()
+        })
+        .collect();
+    state.sort();
+    state
+}
+
+/// Waits `interval`, returning `false` early if Ctrl-C is pressed meanwhile.
+pub async fn sleep(interval: Duration, shutdown: &Shutdown) -> bool {
+    let step = Duration::from_secs(1);
+    let mut waited = Duration::ZERO;
+    while waited < interval {
+        if shutdown.requested() {
+            return false;
+        }
+        tokio::time::sleep(step.min(interval - waited)).await;
+        waited += step;
+    }
+    !shutdown.requested()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::Status;
+
+    #[test]
+    fn parses_intervals_and_spots_changes() {
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_interval("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert!(parse_interval("0m").is_err());
+        assert!(parse_interval("15x").is_err());
+        assert!(parse_interval("m").is_err());
+
+        let comparison = |deployed| Comparison {
+            expected: Some("1.2.0".to_string()),
+            deployed,
+            ..Comparison::new("scraper", Status::Drift)
+        };
+        let before = fingerprint(&[comparison(Some("1.0.0"))]);
+        assert_eq!(before, fingerprint(&[comparison(Some("1.0.0"))]));
+        assert_ne!(before, fingerprint(&[comparison(Some("1.1.0"))]));
+    }
+
+    #[test]
+    fn rejects_intervals_too_large_to_represent() {
+        assert_eq!(
+            parse_interval("99999999999999999h"),
+            Err("`99999999999999999h` is too large an interval".to_string())
+        );
+        assert!(parse_interval("99999999999999999999").is_err());
+    }
+}